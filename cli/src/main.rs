@@ -1,13 +1,28 @@
+use std::fs;
+use std::time::Duration;
+
 use argh::FromArgs;
 
 use rand::Rng;
-use sol_lib::board::Board;
-use sol_lib::generator::{self, Puzzle, RandomRange};
+use sol_lib::board::constants::{BOARD_SIZE, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use sol_lib::board::{
+    Board, BoardOptions, Topology,
+    cmove::CMove,
+    piece::PieceKind,
+    ruleset::{ChainCapture, Chameleon, Promotion, Ruleset},
+    square::Square,
+};
+use sol_lib::generator::{
+    self, DifficultyTarget, Puzzle, RandomRange, SolutionBounds, Strategy, TargetSurvivor,
+};
+use sol_lib::record::GameRecord;
+use sol_lib::solver::{Solver, budget::SearchBudget, difficulty::DifficultyBucket};
 
 fn main() {
     let args: Args = argh::from_env();
+    let budget = search_budget(args.time_limit, args.max_nodes);
     if args.generate {
-        let puzzle = generate_puzzle(args.num_pieces, args.solutions);
+        let puzzle = generate_puzzle(&args, budget.clone());
         let Some(puzzle) = puzzle else {
             println!("Failed to generate a puzzle, try adjusting the generation parameters");
             return;
@@ -15,7 +30,7 @@ fn main() {
 
         puzzle.board.pretty_print();
         if args.print {
-            print_solutions(&puzzle);
+            print_solutions(&puzzle.board, args.all, args.rate, budget);
         }
 
         return;
@@ -23,65 +38,229 @@ fn main() {
 
     let board = if let Some(board_string) = args.solve_board {
         Board::from_string(board_string)
+    } else if let Some(fen) = args.solve_fen {
+        Board::from_fen(&fen)
     } else if let Some(board_id) = args.solve {
         Board::from_id(&board_id)
+    } else if let Some(path) = args.load {
+        let record = match fs::read_to_string(&path) {
+            Ok(record) => record,
+            Err(e) => {
+                println!("Could not read {}: {}", path, e);
+                return;
+            }
+        };
+        GameRecord::parse(&record).and_then(|record| record.replay())
     } else {
         println!("Use --help to see available options");
         return;
     };
-    let Ok(board) = board else {
-        println!("Invalid board string/id");
-        return;
+    let mut board = match board {
+        Ok(board) => board,
+        Err(e) => {
+            println!("Invalid board string/id/record: {}", e);
+            return;
+        }
     };
 
-    let puzzle = board.solve();
-    puzzle.board.pretty_print();
-    print_solutions(&puzzle);
+    if let Some(moves) = args.moves {
+        for notation in moves.split_whitespace() {
+            match CMove::parse(&board, notation) {
+                Ok(mv) => {
+                    board.make_move(mv);
+                }
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    if args.record {
+        println!("{}", GameRecord::from_board(&board));
+    }
+
+    board.pretty_print();
+    print_solutions(&board, args.all, args.rate, budget);
 }
 
-fn print_solutions(puzzle: &Puzzle) {
-    let solutions = &puzzle.solutions;
-    if solutions.len() == 0 {
-        println!("No solutions found");
+fn search_budget(time_limit: Option<u64>, max_nodes: Option<u64>) -> SearchBudget {
+    let mut budget = SearchBudget::unlimited();
+    if let Some(secs) = time_limit {
+        budget = budget.with_time_limit(Duration::from_secs(secs));
+    }
+
+    if let Some(max_nodes) = max_nodes {
+        budget = budget.with_max_nodes(max_nodes);
+    }
+
+    budget
+}
+
+/// Prints solutions as the solver finds them, only the first one unless
+/// `all` is set, and the difficulty of the board if `rate` is set. Every
+/// search draws from the same `budget`.
+fn print_solutions(board: &Board, all: bool, rate: bool, budget: SearchBudget) {
+    let mut solutions = Solver::with_budget(board, budget.clone()).solutions(board);
+    let Some(solution) = solutions.next() else {
+        if solutions.budget_exhausted() {
+            println!("Search budget exhausted before a solution was found");
+        } else {
+            println!("No solutions found");
+        }
+        return;
+    };
+
+    print_solution(&solution);
+    if all {
+        for solution in solutions.by_ref() {
+            println!();
+            print_solution(&solution);
+        }
+
+        if solutions.budget_exhausted() {
+            println!("Search budget exhausted, not every solution was printed");
+        }
+    }
+
+    let nodes = solutions.nodes();
+    let mut solver = Solver::with_budget(board, budget.remaining(nodes));
+    match solver.count(&board.position()) {
+        Ok(count) => println!("There are atleast {} solutions to this puzzle", count),
+        Err(_) => println!("Search budget exhausted before the solutions could be counted"),
+    }
+
+    if !rate {
         return;
     }
-    let solution = solutions.first().unwrap();
-    let mut idx = 0;
-    solution.iter().for_each(|m| {
-        idx += 1;
-        println!("{}. {}", idx, m.notation());
-    });
-    println!(
-        "There are atleast {} solutions to this puzzle",
-        solutions.len()
-    );
+
+    match board.difficulty_within(budget.remaining(nodes + solver.nodes())) {
+        Ok(difficulty) => println!("Difficulty: {}", difficulty),
+        Err(_) => println!("Search budget exhausted before the difficulty could be rated"),
+    }
 }
 
-fn generate_puzzle(num_pieces: Option<u32>, num_solutions: Option<u32>) -> Option<Puzzle> {
-    let mut num_pieces = num_pieces.unwrap_or(5);
+fn print_solution(solution: &[CMove]) {
+    for (idx, m) in solution.iter().enumerate() {
+        println!("{}. {}", idx + 1, m.notation());
+    }
+}
+
+/// Generates a puzzle from the generation options in `args`
+fn generate_puzzle(args: &Args, budget: SearchBudget) -> Option<Puzzle> {
+    let width = args
+        .width
+        .unwrap_or(BOARD_SIZE)
+        .clamp(MIN_BOARD_SIZE, MAX_BOARD_SIZE);
+    let height = args
+        .height
+        .unwrap_or(BOARD_SIZE)
+        .clamp(MIN_BOARD_SIZE, MAX_BOARD_SIZE);
+
+    let mut num_pieces = args.num_pieces.unwrap_or(5);
     if num_pieces < 2 {
         num_pieces = 2;
     }
 
-    let mut num_solutions = num_solutions.unwrap_or(5);
-    if num_solutions < 1 {
-        num_solutions = 5;
+    let mut solutions = args.solutions.unwrap_or(SolutionBounds::at_most(5));
+    solutions.merge_orders = args.merge_orders;
+
+    let mut blocked_mask: u64 = 0;
+    for notation in &args.blocked {
+        let Ok(square) = Square::parse(&format!(".{}", notation), width, height) else {
+            println!("Invalid blocked square '{}'", notation);
+            return None;
+        };
+
+        blocked_mask |= 1 << (square.rank * width + square.file);
+    }
+
+    if args.solo && args.promotion == Some(Promotion::To(PieceKind::King)) {
+        println!("Pawns can not promote to a king in a Solo Chess puzzle");
+        return None;
+    }
+
+    let num_squares = (width * height) as u32 - blocked_mask.count_ones();
+    if num_pieces > num_squares {
+        num_pieces = num_squares;
     }
 
     println!(
-        "Generating a puzzle with {} pieces with a maximum of {} solutions",
-        num_pieces, num_solutions
+        "Generating a {}x{} puzzle with {} pieces with {} solutions",
+        width, height, num_pieces, solutions
     );
-    let gen_result =
-        generator::generate_weighted_random(num_pieces, num_solutions, num_pieces, &RandRngImpl);
+    let mut rules = match args.solo {
+        true => Ruleset::solo_chess(num_pieces),
+        false => Ruleset::with_age_limit(num_pieces),
+    };
+    if let Some(promotion) = args.promotion {
+        rules.add(promotion);
+    }
+    if args.chameleon {
+        rules.add(Chameleon);
+    }
+    if args.chain {
+        rules.add(ChainCapture);
+    }
+    let options = BoardOptions {
+        rules,
+        width,
+        height,
+        blocked: blocked_mask,
+        topology: args.topology.unwrap_or_default(),
+        ..Default::default()
+    };
+    let strategy = args.strategy.unwrap_or_default();
+    let gen_result = match args.difficulty.clone() {
+        Some(mut target) => {
+            if let Some(attempts) = args.attempts {
+                target.attempts = attempts;
+            }
+
+            generator::generate_with_difficulty(
+                num_pieces,
+                solutions,
+                options,
+                strategy,
+                target,
+                budget.clone(),
+                &RandRngImpl,
+            )
+        }
+        None => strategy.generate(num_pieces, solutions, options, budget.clone(), &RandRngImpl),
+    };
     gen_result.print_stats();
+    let nodes = gen_result.nodes();
+    if gen_result.budget_exhausted {
+        println!("Search budget exhausted, try a larger budget");
+        return None;
+    }
 
     let Some(puzzle) = gen_result.puzzle() else {
         println!("Failed to generate a puzzle, try again");
         return None;
     };
 
-    Some(puzzle)
+    let target = args.target.unwrap_or_default();
+    let targeted = generator::designate_target(
+        puzzle,
+        target,
+        solutions,
+        budget.remaining(nodes),
+        &RandRngImpl,
+    );
+    match targeted {
+        Ok(Some(puzzle)) => Some(puzzle),
+        Ok(None) => {
+            println!("No target leaves enough solutions, try again");
+            None
+        }
+        Err(_) => {
+            println!("Search budget exhausted, try a larger budget");
+            None
+        }
+    }
 }
 
 /// Solitaire Chess puzzle generator and solver
@@ -97,13 +276,81 @@ struct Args {
     num_pieces: Option<u32>,
 
     #[argh(option)]
-    /// maximum number of solutions allowed for the generated puzzle. atleast 1. defaults to 5
-    solutions: Option<u32>,
+    /// number of files on the board while generating a puzzle. defaults to 4
+    width: Option<usize>,
+
+    #[argh(option)]
+    /// number of ranks on the board while generating a puzzle. defaults to 4
+    height: Option<usize>,
+
+    #[argh(option)]
+    /// a square that is cut out of the board while generating a puzzle, eg: "b2". can be repeated
+    blocked: Vec<String>,
+
+    #[argh(option, from_str_fn(parse_solutions))]
+    /// number of solutions allowed for the generated puzzle, either a maximum, a range like "2-4" or "unique". defaults to 5
+    solutions: Option<SolutionBounds>,
+
+    #[argh(switch)]
+    /// count capture orders that leave the same last piece on the same square as one solution
+    merge_orders: bool,
+
+    #[argh(option, from_str_fn(parse_topology))]
+    /// let the edges of the generated board wrap around, either "cylinder" for the files or "torus" for the files and the ranks
+    topology: Option<Topology>,
+
+    #[argh(option, from_str_fn(parse_strategy))]
+    /// how the generated puzzle is put together, either "random" to place random pieces that keep the board solvable or "retrograde" to undo captures from the last piece standing. defaults to random
+    strategy: Option<Strategy>,
+
+    #[argh(option, from_str_fn(parse_difficulty))]
+    /// keep generating until the puzzle is rated "easy", "medium", "hard" or "expert", or within a range of scores from 0 to 100, eg: "40-60"
+    difficulty: Option<DifficultyTarget>,
+
+    #[argh(option)]
+    /// number of random puzzles to improve on before giving up on the --difficulty. defaults to 20
+    attempts: Option<u32>,
+
+    #[argh(switch)]
+    /// generate a Solo Chess puzzle, where the king can never be captured and has to be the last piece standing
+    solo: bool,
+
+    #[argh(option, from_str_fn(parse_target))]
+    /// what has to be left standing in the generated puzzle, either "piece" for one particular piece or "square" for the last piece on one particular square
+    target: Option<TargetSurvivor>,
+
+    #[argh(option, from_str_fn(parse_promotion))]
+    /// let pawns that capture onto the top rank promote, either to a piece of the player's "choice" or always to the piece given by its letter, eg: "Q"
+    promotion: Option<Promotion>,
+
+    #[argh(switch)]
+    /// generate a puzzle where every piece turns into the piece it captures
+    chameleon: bool,
+
+    #[argh(switch)]
+    /// generate a puzzle where after the first capture only the piece that captured last may move
+    chain: bool,
 
     #[argh(switch)]
     /// print the solution. When solving a puzzle, this is always set to true
     print: bool,
 
+    #[argh(switch)]
+    /// print every solution as it is found, instead of only the first one
+    all: bool,
+
+    #[argh(switch)]
+    /// rate the difficulty of the puzzle along with its solutions, which searches every line of play
+    rate: bool,
+
+    #[argh(option)]
+    /// give up generating or solving after this many seconds
+    time_limit: Option<u64>,
+
+    #[argh(option)]
+    /// give up generating or solving after searching this many positions
+    max_nodes: Option<u64>,
+
     #[argh(option, short = 's')]
     /// the id of the board to solve
     solve: Option<String>,
@@ -111,6 +358,85 @@ struct Args {
     #[argh(option)]
     /// the board to solve in board representation
     solve_board: Option<String>,
+
+    #[argh(option)]
+    /// the board to solve in FEN like notation, eg: "PB2/4/BR[1]2/P2P 3"
+    solve_fen: Option<String>,
+
+    #[argh(option)]
+    /// path to a game record to load the board from, after its moves
+    load: Option<String>,
+
+    #[argh(switch)]
+    /// print the game record of the moves played, in a PGN like format
+    record: bool,
+
+    #[argh(option, short = 'm')]
+    /// moves to play before solving, separated by spaces. eg: "RxBb4 Rxa4"
+    moves: Option<String>,
+}
+
+fn parse_target(value: &str) -> Result<TargetSurvivor, String> {
+    match value {
+        "piece" => Ok(TargetSurvivor::Piece),
+        "square" => Ok(TargetSurvivor::Square),
+        _ => Err("expected piece or square".to_string()),
+    }
+}
+
+fn parse_strategy(value: &str) -> Result<Strategy, String> {
+    match value {
+        "random" => Ok(Strategy::WeightedRandom),
+        "retrograde" => Ok(Strategy::Retrograde),
+        _ => Err("expected random or retrograde".to_string()),
+    }
+}
+
+fn parse_solutions(value: &str) -> Result<SolutionBounds, String> {
+    let bounds = match value.split_once('-') {
+        _ if value == "unique" => Some(SolutionBounds::unique()),
+        Some((min, max)) => min
+            .parse()
+            .ok()
+            .zip(max.parse().ok())
+            .map(|(min, max)| SolutionBounds::new(min, max)),
+        None => value.parse().ok().map(SolutionBounds::at_most),
+    };
+
+    match bounds {
+        Some(bounds) if bounds.min >= 1 && bounds.min <= bounds.max => Ok(bounds),
+        _ => Err("expected a number of solutions, a range like 2-4 or unique".to_string()),
+    }
+}
+
+fn parse_difficulty(value: &str) -> Result<DifficultyTarget, String> {
+    if let Some(bucket) = DifficultyBucket::parse(value) {
+        return Ok(DifficultyTarget::bucket(bucket));
+    }
+
+    let scores = value
+        .split_once('-')
+        .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)));
+    match scores {
+        Some((min, max)) if min < max => Ok(DifficultyTarget::new(min, max)),
+        _ => Err("expected easy, medium, hard, expert or a range like 40-60".to_string()),
+    }
+}
+
+fn parse_topology(value: &str) -> Result<Topology, String> {
+    match value {
+        "cylinder" => Ok(Topology::Cylinder),
+        "torus" => Ok(Topology::Torus),
+        _ => Err("expected cylinder or torus".to_string()),
+    }
+}
+
+fn parse_promotion(value: &str) -> Result<Promotion, String> {
+    match (value, PieceKind::parse(value)) {
+        ("choice", _) => Ok(Promotion::Choice),
+        (_, Ok(Some(kind))) => Ok(Promotion::To(kind)),
+        _ => Err("expected choice or a piece letter".to_string()),
+    }
 }
 
 struct RandRngImpl;
@@ -6,7 +6,10 @@ use std::collections::HashMap;
 
 use crate::{resources::Resources, widgets::*};
 use macroquad::prelude::*;
-use sol_lib::generator::Puzzle;
+use sol_lib::{
+    board::Topology,
+    generator::{Puzzle, Strategy, TargetSurvivor},
+};
 
 #[derive(Default)]
 pub struct Game {
@@ -28,6 +31,10 @@ pub struct Game {
     heading_text: String,
     heading: LabelWidget,
 
+    // Shown under the heading when a new puzzle could not be generated
+    notice: Option<&'static str>,
+    notice_rect: Rect,
+
     rules_btn_text: String,
     rules_btn: ButtonWidget,
 
@@ -47,6 +54,12 @@ pub struct GameSettings {
     pub max_moves_per_piece: u32,
     pub debug: bool,
     pub num_pieces: u32,
+    pub target_survivor: TargetSurvivor,
+    pub promotion: bool,
+    pub chameleon: bool,
+    pub chain_capture: bool,
+    pub topology: Topology,
+    pub strategy: Strategy,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
@@ -55,6 +68,7 @@ pub enum GameMode {
     Medium,
     Hard,
     Custom,
+    Solo,
 }
 
 impl Default for GameMode {
@@ -8,12 +8,24 @@ pub const SCREEN_WIDTH_MAX: f32 = 10000.0;
 // How big the square should be relative to the screen size, between 0 and 1
 pub const BOARD_SQUARE_WIDTH_MULTIPLIER: f32 = 0.11;
 
+// Number of squares of the default board that the board area is sized to
+pub const BOARD_AREA_SQUARES: f32 = 4.0;
+
+// Positions the solver may search while generating a puzzle, so that a hard
+// setting cannot freeze the frame loop
+pub const GENERATE_MAX_NODES: u64 = 500_000;
+pub const HARD_PUZZLE_ATTEMPTS: u32 = 5;
+
+// Played when the first puzzle can not be generated within the budget
+pub const STARTER_PUZZLE: &str = "RN2/4/B3/P2Q 2";
+
 pub const BOARD_SHADOW_MULTIPLIER: f32 = 0.1;
 
 pub const BOARD_PIECE_WIDTH_MULTIPLIER: f32 = 0.8;
 
 // How big the heading text font size should be relative to the screen size, between 0 and 1
 pub const HEADING_FONT_SIZE_MULTIPLIER: f32 = 0.07;
+pub const NOTICE_FONT_SIZE_MULTIPLIER: f32 = 0.025;
 
 // Height of the button relative to the screen size.
 pub const BUTTON_HEIGHT_MULTIPLIER: f32 = 0.08;
@@ -39,7 +51,44 @@ pub const EASY_BUTTON_TEXT: &str = "EASY";
 pub const MEDIUM_BUTTON_TEXT: &str = "MEDIUM";
 pub const HARD_BUTTON_TEXT: &str = "HARD";
 pub const CUSTOM_BUTTON_TEXT: &str = "CUSTOM";
+pub const SOLO_BUTTON_TEXT: &str = "SOLO";
 pub const GENERATE_BUTTON_TEXT: &str = "GENERATE";
 pub const PIECES_LABEL_TEXT: &str = "Pieces";
 pub const AGE_LABEL_TEXT: &str = "   Age";
+pub const NO_PUZZLE_TEXT: &str = "No new puzzle was found in time, try again";
+pub const RULES_TEXT: &str = "\
+    Every move should be a \n\
+    capture. Win when only \n\
+    one piece is left.\n\
+    Age: Each piece can only \n\
+    move 'age' times";
+pub const SOLO_RULES_TEXT: &str = "\
+    Every move should be a \n\
+    capture. The king can't \n\
+    be captured, win when \n\
+    only the king is left.\n\
+    Age: Each piece can only \n\
+    move 'age' times";
+pub const TARGET_PIECE_RULES_TEXT: &str = "\
+    Target: the marked piece \n\
+    has to be the last one";
+pub const TARGET_SQUARE_RULES_TEXT: &str = "\
+    Target: the last piece \n\
+    has to end on the mark";
+pub const PROMOTION_RULES_TEXT: &str = "\
+    Promotion: a pawn taking \n\
+    onto the top rank is \n\
+    promoted";
+pub const CHAMELEON_RULES_TEXT: &str = "\
+    Chameleon: a piece turns \n\
+    into the piece it takes";
+pub const CHAIN_RULES_TEXT: &str = "\
+    Chain: only the piece that \n\
+    took last can move";
+pub const CYLINDER_RULES_TEXT: &str = "\
+    Cylinder: the left and \n\
+    right edges meet";
+pub const TORUS_RULES_TEXT: &str = "\
+    Torus: opposite edges \n\
+    meet";
 
@@ -1,6 +1,7 @@
 use super::{Game, constants};
 use crate::{game::GameMode, widgets::*};
 use macroquad::{math, prelude::*};
+use sol_lib::board::Topology;
 
 impl Game {
     fn initialize_drawables(&mut self, new_width: f32, new_height: f32) {
@@ -8,13 +9,14 @@ impl Game {
         self.window_width = new_width;
 
         let min_dimension = f32::min(self.window_height, self.window_width);
+        // The rest of the layout is measured in squares of a 4x4 board, larger
+        // boards shrink their squares to fit in the same area.
         let square_width = constants::BOARD_SQUARE_WIDTH_MULTIPLIER * min_dimension;
-        let num_squares = self.board.num_squares;
-        let board_width = square_width * num_squares as f32;
+        let board_width = square_width * constants::BOARD_AREA_SQUARES;
         let board_x = (self.window_width * 0.6 - board_width) / 2.0;
         let board_y = (self.window_height - board_width) / 2.0;
         let board_rect = Rect::new(board_x, board_y, board_width, board_width);
-        self.board.initialize_drawables(square_width, board_rect);
+        self.board.initialize_drawables(board_rect);
 
         let heading_font_size = constants::HEADING_FONT_SIZE_MULTIPLIER * min_dimension;
         let f = heading_font_size.floor() as u16;
@@ -35,6 +37,14 @@ impl Game {
         self.heading
             .initialize_drawables(heading_rect, heading_font_size, &self.resources);
 
+        // Between the heading and the board
+        self.notice_rect = Rect::new(
+            board_x,
+            board_y - constants::TOP_HEADING_OFFSET_MULTIPLIER * square_width / 2.,
+            board_width,
+            constants::NOTICE_FONT_SIZE_MULTIPLIER * min_dimension,
+        );
+
         // Buttons
         let btn_h = constants::BUTTON_HEIGHT_MULTIPLIER * min_dimension;
         let btn_w = constants::BUTTON_WIDTH_MULTIPLIER * board_width;
@@ -109,6 +119,9 @@ impl Game {
                 btn_h,
             ));
 
+        self.get_game_mode_button(&GameMode::Solo)
+            .initialize_drawables(Rect::new(right_column_x, bottom_row_y, btn_w, btn_h));
+
         // Right column 2
         let right_column_2_x =
             right_column_x + btn_w + constants::RIGHT_COLUMN_OFFSET_MULTIPLIER * square_width;
@@ -168,6 +181,7 @@ impl Game {
         self.update_window_size();
 
         self.draw_heading();
+        self.draw_notice();
         self.draw_board();
         self.draw_id_text_button();
         self.draw_buttons();
@@ -179,15 +193,64 @@ impl Game {
         self.heading.draw(&self.resources);
     }
 
+    fn draw_notice(&self) {
+        let Some(notice) = self.notice else {
+            return;
+        };
+
+        let params = TextParams {
+            font: Some(&self.resources.font()),
+            font_size: self.notice_rect.h as u16,
+            color: MAROON,
+            ..Default::default()
+        };
+        draw_text_ex(notice, self.notice_rect.x, self.notice_rect.y, params);
+    }
+
     fn draw_board(&mut self) {
+        let rules_text = self.rules_text();
         let params = BoardDrawParams {
             show_rules: self.show_rules,
             rules_font_size: self.rules_font_size,
+            rules_text: &rules_text,
         };
 
         self.board.draw(&params, &self.resources, &self.settings);
     }
 
+    // The rules of the puzzle being played, with a paragraph for each
+    // variant it is played with
+    fn rules_text(&self) -> String {
+        let board = &self.puzzle.board;
+        let rules = board.rules();
+        let mut paragraphs = vec![match rules.royal_king() {
+            true => constants::SOLO_RULES_TEXT,
+            false => constants::RULES_TEXT,
+        }];
+        if board.position().target() != 0 {
+            paragraphs.push(constants::TARGET_PIECE_RULES_TEXT);
+        }
+        if rules.target_square().is_some() {
+            paragraphs.push(constants::TARGET_SQUARE_RULES_TEXT);
+        }
+        if !rules.promotions().is_empty() {
+            paragraphs.push(constants::PROMOTION_RULES_TEXT);
+        }
+        if rules.chameleon() {
+            paragraphs.push(constants::CHAMELEON_RULES_TEXT);
+        }
+        if rules.chain_capture() {
+            paragraphs.push(constants::CHAIN_RULES_TEXT);
+        }
+        match board.topology() {
+            Topology::Flat => {}
+            Topology::Cylinder => paragraphs.push(constants::CYLINDER_RULES_TEXT),
+            Topology::Torus => paragraphs.push(constants::TORUS_RULES_TEXT),
+        }
+
+        paragraphs.join("\n")
+    }
+
     fn get_game_mode_button<'a>(&'a mut self, mode: &GameMode) -> &'a mut ButtonWidget {
         self.game_mode_btns.get_mut(mode).unwrap()
     }
@@ -234,6 +297,12 @@ impl Game {
             constants::CUSTOM_BUTTON_TEXT,
         );
 
+        self.draw_game_mode_button(
+            &GameMode::Solo,
+            &UiColor::Green,
+            constants::SOLO_BUTTON_TEXT,
+        );
+
         self.generate_btn.draw(
             constants::GENERATE_BUTTON_TEXT,
             &UiColor::Pink,
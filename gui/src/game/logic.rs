@@ -7,8 +7,14 @@ use crate::{
 };
 use macroquad::prelude::*;
 use sol_lib::{
-    board::BoardState,
-    generator::{self, Puzzle, RandomRange},
+    board::{
+        Board, BoardOptions, BoardState, Topology,
+        ruleset::{ChainCapture, Chameleon, Promotion, Ruleset},
+    },
+    generator::{
+        self, DifficultyTarget, Puzzle, RandomRange, SolutionBounds, Strategy, TargetSurvivor,
+    },
+    solver::{Solver, budget::SearchBudget, difficulty::DifficultyBucket},
 };
 
 impl Game {
@@ -21,14 +27,16 @@ impl Game {
             num_pieces: 2,
             ..Default::default()
         };
-        let puzzle = Game::generate_puzzle(game_mode, &settings);
-        let board = BoardWidget::initialize_state(puzzle.board.size, puzzle.board.clone());
+        let puzzle =
+            Game::generate_puzzle(game_mode, &settings).unwrap_or_else(Game::starter_puzzle);
+        let board = BoardWidget::initialize_state(puzzle.board.clone());
         let mut game_mode_btns = HashMap::new();
         let game_mode = GameMode::Medium;
         game_mode_btns.insert(GameMode::Easy, ButtonWidget::initialize_state(true));
         game_mode_btns.insert(GameMode::Medium, ButtonWidget::initialize_state(true));
         game_mode_btns.insert(GameMode::Hard, ButtonWidget::initialize_state(true));
         game_mode_btns.insert(GameMode::Custom, ButtonWidget::initialize_state(true));
+        game_mode_btns.insert(GameMode::Solo, ButtonWidget::initialize_state(true));
         let reset_btn = ButtonWidget::initialize_state(true);
         let next_btn = ButtonWidget::initialize_state(false);
         let rules_btn = ButtonWidget::initialize_state(true);
@@ -227,18 +235,92 @@ impl Game {
             return;
         }
 
+        if is_key_released(KeyCode::T) {
+            self.toggle_setting(|settings| {
+                settings.target_survivor = match settings.target_survivor {
+                    TargetSurvivor::Any => TargetSurvivor::Piece,
+                    TargetSurvivor::Piece => TargetSurvivor::Square,
+                    TargetSurvivor::Square => TargetSurvivor::Any,
+                };
+            });
+            return;
+        }
+
+        if is_key_released(KeyCode::P) {
+            self.toggle_setting(|settings| settings.promotion = !settings.promotion);
+            return;
+        }
+
+        if is_key_released(KeyCode::C) {
+            self.toggle_setting(|settings| settings.chameleon = !settings.chameleon);
+            return;
+        }
+
+        if is_key_released(KeyCode::K) {
+            self.toggle_setting(|settings| settings.chain_capture = !settings.chain_capture);
+            return;
+        }
+
+        if is_key_released(KeyCode::W) {
+            self.toggle_setting(|settings| {
+                settings.topology = match settings.topology {
+                    Topology::Flat => Topology::Cylinder,
+                    Topology::Cylinder => Topology::Torus,
+                    Topology::Torus => Topology::Flat,
+                };
+            });
+            return;
+        }
+
+        if is_key_released(KeyCode::G) {
+            self.toggle_setting(|settings| {
+                settings.strategy = match settings.strategy {
+                    Strategy::WeightedRandom => Strategy::Retrograde,
+                    Strategy::Retrograde => Strategy::WeightedRandom,
+                };
+            });
+            return;
+        }
+
+        if is_key_released(KeyCode::U) {
+            if self.board.undo() {
+                play_sound_once(
+                    &self.resources.sound(&SoundKind::Click),
+                    self.settings.volume,
+                );
+                self.next_btn.is_active = false;
+            }
+            return;
+        }
+
         if is_key_released(KeyCode::Q) {
             std::process::exit(0);
         }
     }
 
+    // Settings toggled from the keyboard change how puzzles are generated,
+    // so each toggle starts a new puzzle
+    fn toggle_setting(&mut self, toggle: impl FnOnce(&mut GameSettings)) {
+        toggle(&mut self.settings);
+        play_sound_once(
+            &self.resources.sound(&SoundKind::Button),
+            self.settings.volume,
+        );
+        self.next_puzzle();
+    }
+
     fn reset(&mut self) {
         self.reset_game(Default::default());
     }
 
     fn reset_game(&mut self, options: ResetOptions) {
+        // Keep the current puzzle if generation ran out of budget, and say so
+        self.notice = None;
         if options.create_new_puzzle {
-            self.puzzle = Game::generate_puzzle(self.game_mode, &self.settings);
+            match Game::generate_puzzle(self.game_mode, &self.settings) {
+                Some(puzzle) => self.puzzle = puzzle,
+                None => self.notice = Some(constants::NO_PUZZLE_TEXT),
+            }
         }
 
         self.next_btn.is_active = false;
@@ -252,12 +334,21 @@ impl Game {
         });
     }
 
-    fn generate_puzzle(mode: GameMode, settings: &GameSettings) -> Puzzle {
+    /// A fixed puzzle to start with when generating the first one runs out of
+    /// budget
+    fn starter_puzzle() -> Puzzle {
+        Board::from_fen(constants::STARTER_PUZZLE)
+            .expect("The starter puzzle is valid")
+            .solve()
+    }
+
+    fn generate_puzzle(mode: GameMode, settings: &GameSettings) -> Option<Puzzle> {
         let piece_count = match mode {
             GameMode::Easy => 3,
             GameMode::Medium => 5,
             GameMode::Hard => 7,
             GameMode::Custom => settings.num_pieces,
+            GameMode::Solo => 6,
         };
 
         let max_moves_per_piece = match mode {
@@ -265,16 +356,80 @@ impl Game {
             GameMode::Medium => 2,
             GameMode::Hard => 2,
             GameMode::Custom => settings.max_moves_per_piece,
+            GameMode::Solo => 2,
         };
 
-        let generated = generator::generate_weighted_random(
-            piece_count,
-            100,
-            max_moves_per_piece,
+        let mut rules = match mode {
+            GameMode::Solo => Ruleset::solo_chess(max_moves_per_piece),
+            _ => Ruleset::with_age_limit(max_moves_per_piece),
+        };
+        if settings.promotion {
+            rules.add(Promotion::Choice);
+        }
+        if settings.chameleon {
+            rules.add(Chameleon);
+        }
+        if settings.chain_capture {
+            rules.add(ChainCapture);
+        }
+        let options = BoardOptions {
+            rules,
+            topology: settings.topology,
+            ..Default::default()
+        };
+        let budget = SearchBudget::unlimited().with_max_nodes(constants::GENERATE_MAX_NODES);
+
+        // Hard puzzles have to be rated hard
+        let generated = match mode {
+            GameMode::Hard => {
+                let target = DifficultyTarget {
+                    attempts: constants::HARD_PUZZLE_ATTEMPTS,
+                    ..DifficultyTarget::bucket(DifficultyBucket::Hard)
+                };
+                generator::generate_with_difficulty(
+                    piece_count,
+                    SolutionBounds::at_most(100),
+                    options,
+                    settings.strategy,
+                    target,
+                    budget.clone(),
+                    &MacroquadRandAdapter,
+                )
+            }
+            _ => settings.strategy.generate(
+                piece_count,
+                SolutionBounds::at_most(100),
+                options,
+                budget.clone(),
+                &MacroquadRandAdapter,
+            ),
+        };
+        let mut budget = budget.remaining(generated.nodes());
+
+        // When none turns up in time, settle for the closest one that is still
+        // rated at least medium
+        let closest = generated
+            .closest()
+            .filter(|(_, difficulty)| difficulty.bucket >= DifficultyBucket::Medium)
+            .map(|(board, _)| board.clone());
+        let puzzle = match closest {
+            Some(board) => {
+                let mut solver = Solver::with_budget(&board, budget.clone());
+                let puzzle = solver.solve(&board).ok()?;
+                budget = budget.remaining(solver.nodes());
+                puzzle
+            }
+            None => generated.puzzle()?,
+        };
+        generator::designate_target(
+            puzzle,
+            settings.target_survivor,
+            SolutionBounds::at_most(100),
+            budget,
             &MacroquadRandAdapter,
-        );
-        let puzzle = generated.puzzle();
-        puzzle.expect("No puzzle was generated")
+        )
+        .ok()
+        .flatten()
     }
 }
 
@@ -67,23 +67,27 @@ pub async fn init() -> Resources {
 
 fn generate_texture_rects_pieces(active: bool) -> HashMap<PieceKind, Rect> {
     let mut texture_rects_pieces = HashMap::new();
-    texture_rects_pieces.insert(PieceKind::Pawn, piece_texture_rect(PieceKind::Pawn, active));
-    texture_rects_pieces.insert(PieceKind::Knight, piece_texture_rect(PieceKind::Knight, active));
-    texture_rects_pieces.insert(PieceKind::Bishop, piece_texture_rect(PieceKind::Bishop, active));
-    texture_rects_pieces.insert(PieceKind::Rook, piece_texture_rect(PieceKind::Rook, active));
-    texture_rects_pieces.insert(PieceKind::Queen, piece_texture_rect(PieceKind::Queen, active));
-    texture_rects_pieces.insert(PieceKind::King, piece_texture_rect(PieceKind::King, active));
+    for kind in PieceKind::ALL {
+        texture_rects_pieces.insert(kind, piece_texture_rect(kind, active));
+    }
 
     texture_rects_pieces
 }
 
+// Fairy pieces have no sprites of their own, they borrow the sprite of the
+// piece they move most like and get their letter drawn on top. Pieces defined
+// at runtime could move like anything, they borrow the knight.
 fn piece_texture_rect(piece: PieceKind, active: bool) -> Rect {
     let index = match piece {
         PieceKind::Pawn => 0,
-        PieceKind::Knight => 1,
-        PieceKind::Bishop => 2,
-        PieceKind::Rook => 3,
-        PieceKind::Queen => 4,
+        PieceKind::Knight
+        | PieceKind::Camel
+        | PieceKind::Zebra
+        | PieceKind::Nightrider
+        | PieceKind::Custom(_) => 1,
+        PieceKind::Bishop | PieceKind::Archbishop => 2,
+        PieceKind::Rook | PieceKind::Chancellor => 3,
+        PieceKind::Queen | PieceKind::Amazon => 4,
         PieceKind::King => 5,
     };
     let color = match active {
@@ -111,10 +115,13 @@ impl Resources {
             &self.texture_rects_inactive_pieces
         };
 
-        let texture_rect = texture_rects_lookup.get(&piece.kind).unwrap();
+        let texture_rect = texture_rects_lookup
+            .get(&piece.kind)
+            .copied()
+            .unwrap_or_else(|| piece_texture_rect(piece.kind, piece.active));
         Texture {
             texture: texture,
-            texture_rect: *texture_rect,
+            texture_rect,
         }
     }
 
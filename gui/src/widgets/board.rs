@@ -8,7 +8,7 @@ use crate::{
 };
 use macroquad::prelude::*;
 use sol_lib::{
-    board::{Board, BoardState, piece::Piece},
+    board::{Board, BoardState, cmove::CMove, piece::Piece},
     generator::Puzzle,
 };
 
@@ -16,7 +16,9 @@ use sol_lib::{
 pub struct BoardWidget {
     current_board: Board,
     square_width: f32,
-    pub num_squares: usize, // this is a constant = 4 for now.
+    pub width: usize,
+    pub height: usize,
+    board_area: Rect,
     board_rect: Rect,
     squares: Vec<GameSquare>,
     state: GameState,
@@ -28,22 +30,37 @@ pub struct BoardInteraction<'a> {
 }
 
 impl BoardWidget {
-    pub fn initialize_state(num_squares: usize, current_board: Board) -> BoardWidget {
+    pub fn initialize_state(current_board: Board) -> BoardWidget {
         let mut board = BoardWidget::default();
-        board.num_squares = num_squares;
+        board.width = current_board.width;
+        board.height = current_board.height;
         board.current_board = current_board;
         board
     }
 
-    pub fn initialize_drawables(&mut self, square_width: f32, board_rect: Rect) {
-        self.square_width = square_width;
-        self.board_rect = board_rect;
+    /// Lays out the squares to fit inside `board_area`, centering the board
+    /// when it is not as wide as it is tall.
+    pub fn initialize_drawables(&mut self, board_area: Rect) {
+        self.board_area = board_area;
+        self.square_width = f32::min(
+            board_area.w / self.width as f32,
+            board_area.h / self.height as f32,
+        );
+
+        let board_w = self.square_width * self.width as f32;
+        let board_h = self.square_width * self.height as f32;
+        self.board_rect = Rect::new(
+            board_area.x + (board_area.w - board_w) / 2.0,
+            board_area.y + (board_area.h - board_h) / 2.0,
+            board_w,
+            board_h,
+        );
 
         let dark = UiColor::Brown.to_bg_color();
         let light = UiColor::Yellow.to_bg_color();
         let mut rects = Vec::new();
-        for i in 0..self.num_squares {
-            for j in 0..self.num_squares {
+        for i in 0..self.width {
+            for j in 0..self.height {
                 let x_eff = self.board_rect.x + (i as f32 * self.square_width);
                 let y_eff = self.board_rect.y + (j as f32 * self.square_width);
                 let rect = Rect::new(x_eff, y_eff, self.square_width, self.square_width);
@@ -80,13 +97,10 @@ impl BoardWidget {
                 UiColor::Yellow.to_bg_color(),
             );
 
-            let font_size = params.rules_font_size * 0.4;
-            let rules = "\
-                Every move should be a \n\
-                capture. Win when only \n\
-                one piece is left.\n\
-                Age: Each piece can only \n\
-                move 'age' times";
+            // Shrink the text when variants add to the rules
+            let lines = params.rules_text.lines().count().max(6) as f32;
+            let font_size = params.rules_font_size * 0.4 * 6. / lines;
+            let rules = params.rules_text;
             let measurement = measure_text(rules, Some(resources.font()), font_size as u16, 1.0);
             let draw_text_params = TextParams {
                 font_size: font_size as u16,
@@ -107,7 +121,16 @@ impl BoardWidget {
 
         let sprite_size = constants::BOARD_PIECE_WIDTH_MULTIPLIER * self.square_width;
         let mut selected_square = None;
+        let target_square = self
+            .current_board
+            .target_square()
+            .map(|square| (square.file, square.rank));
         self.squares.iter().for_each(|square| {
+            if self.current_board.is_blocked(square.i, square.j) {
+                draw_blocked_square(&square.rect);
+                return;
+            }
+
             let color = match square.is_source {
                 true => square.color,
                 false => match square.is_target {
@@ -124,8 +147,23 @@ impl BoardWidget {
                 color,
             );
 
+            if target_square == Some((square.i, square.j)) {
+                let thickness = 0.06 * square.rect.w;
+                draw_rectangle_lines(
+                    square.rect.x + thickness,
+                    square.rect.y + thickness,
+                    square.rect.w - 2.0 * thickness,
+                    square.rect.h - 2.0 * thickness,
+                    thickness,
+                    UiColor::Blue.to_bg_color(),
+                );
+            }
+
             if let Some(p) = &self.current_board.cells[square.i][square.j] {
                 let offset = (square.rect.w - sprite_size) / 2.0;
+                if p.target && !square.is_source {
+                    draw_target_piece_marker(&square.rect);
+                }
                 let texture_params = piece_draw_texture_params(&p, sprite_size, resources);
                 if !square.is_source {
                     draw_texture_ex(
@@ -135,12 +173,21 @@ impl BoardWidget {
                         WHITE,
                         texture_params.draw_text_params,
                     );
+                    draw_fairy_letter(
+                        p,
+                        square.rect.x + offset,
+                        square.rect.y + offset,
+                        sprite_size,
+                        resources,
+                    );
                 } else {
                     selected_square = Some(square);
                 }
             }
         });
 
+        self.draw_wrapping_edges();
+
         if let Some(selected_square) = selected_square {
             if let Some(p) = self.current_board.cells[selected_square.i][selected_square.j] {
                 let texture_params = piece_draw_texture_params(&p, sprite_size, resources);
@@ -151,16 +198,128 @@ impl BoardWidget {
                     WHITE,
                     texture_params.draw_text_params,
                 );
+                draw_fairy_letter(
+                    &p,
+                    mouse_position().0 - sprite_size / 2.0,
+                    mouse_position().1 - sprite_size / 2.0,
+                    sprite_size,
+                    resources,
+                );
             }
         }
 
+        if let GameState::SelectPromotion(source, target) = self.state {
+            self.draw_promotion_picker(source, target, resources);
+        }
+
         if settings.debug {
             self.draw_debug();
         }
     }
 
+    /// Edges that wrap around to the opposite edge are drawn as dashed
+    /// lines, so that a piece leaving through one is expected to come back
+    /// through the other
+    fn draw_wrapping_edges(&self) {
+        let topology = self.current_board.topology();
+        let rect = self.board_rect;
+        let thickness = 0.05 * self.square_width;
+        let dash = self.square_width / 4.0;
+        let color = UiColor::Blue.to_bg_color();
+        let draw_dashes = |x: f32, y: f32, horizontal: bool, length: f32| {
+            let mut offset = dash / 2.0;
+            while offset < length {
+                match horizontal {
+                    true => draw_line(x + offset, y, x + offset + dash, y, thickness, color),
+                    false => draw_line(x, y + offset, x, y + offset + dash, thickness, color),
+                }
+                offset += 2.0 * dash;
+            }
+        };
+
+        if topology.wraps_files() {
+            draw_dashes(rect.x, rect.y, false, rect.h);
+            draw_dashes(rect.x + rect.w, rect.y, false, rect.h);
+        }
+
+        if topology.wraps_ranks() {
+            draw_dashes(rect.x, rect.y, true, rect.w);
+            draw_dashes(rect.x, rect.y + rect.h, true, rect.w);
+        }
+    }
+
+    /// The pieces a pawn can promote to, in a row over the middle of the board
+    fn draw_promotion_picker(
+        &self,
+        source: (usize, usize),
+        target: (usize, usize),
+        resources: &Resources,
+    ) {
+        let moves = self.moves_between(source, target);
+        let rects = self.promotion_rects(moves.len());
+        if let (Some(first), Some(last)) = (rects.first(), rects.last()) {
+            let picker = Rect::new(first.x, first.y, last.right() - first.x, first.h);
+            draw_shadow(&picker, constants::BOARD_SHADOW_MULTIPLIER * first.w);
+        }
+
+        for (mv, rect) in moves.iter().zip(rects.iter()) {
+            draw_rectangle(
+                rect.x,
+                rect.y,
+                rect.w,
+                rect.h,
+                UiColor::Yellow.to_bg_color(),
+            );
+            let Some(kind) = mv.promotion else {
+                continue;
+            };
+
+            let piece = Piece::new(kind);
+            let sprite_size = constants::BOARD_PIECE_WIDTH_MULTIPLIER * rect.w;
+            let offset = (rect.w - sprite_size) / 2.0;
+            let texture_params = piece_draw_texture_params(&piece, sprite_size, resources);
+            draw_texture_ex(
+                texture_params.texture,
+                rect.x + offset,
+                rect.y + offset,
+                WHITE,
+                texture_params.draw_text_params,
+            );
+            draw_fairy_letter(
+                &piece,
+                rect.x + offset,
+                rect.y + offset,
+                sprite_size,
+                resources,
+            );
+        }
+    }
+
+    fn promotion_rects(&self, count: usize) -> Vec<Rect> {
+        let size = f32::min(self.square_width, self.board_rect.w / count as f32);
+        let x = self.board_rect.x + (self.board_rect.w - size * count as f32) / 2.0;
+        let y = self.board_rect.y + (self.board_rect.h - size) / 2.0;
+        (0..count)
+            .map(|i| Rect::new(x + i as f32 * size, y, size, size))
+            .collect()
+    }
+
+    /// The legal moves from `source` to `target`, more than one only when a
+    /// pawn can promote to different pieces
+    fn moves_between(&self, source: (usize, usize), target: (usize, usize)) -> Vec<CMove> {
+        let mut moves = self
+            .current_board
+            .legal_moves
+            .iter()
+            .filter(|m| (m.from.file, m.from.rank) == source && (m.to.file, m.to.rank) == target)
+            .cloned()
+            .collect::<Vec<_>>();
+        moves.sort_by_key(|m| m.promotion);
+        moves
+    }
+
     fn get(&mut self, i: usize, j: usize) -> &mut GameSquare {
-        &mut self.squares[i * self.num_squares + j]
+        &mut self.squares[i * self.height + j]
     }
 
     fn draw_debug(&self) {
@@ -218,6 +377,9 @@ impl BoardWidget {
                     }
                 }
 
+                GameState::SelectPromotion(source, target) => {
+                    self.handle_select_promotion(mouse_pos, source, target, resources, settings)
+                }
                 GameState::GameOver(previous_target) => GameState::GameOver(previous_target),
             };
 
@@ -231,6 +393,9 @@ impl BoardWidget {
                     self.handle_select_source(mouse_pos, previous_target)
                 }
                 GameState::SelectTarget(source) => GameState::SelectTarget(source),
+                GameState::SelectPromotion(source, target) => {
+                    GameState::SelectPromotion(source, target)
+                }
                 GameState::GameOver(previous_target) => GameState::GameOver(previous_target),
             };
 
@@ -246,20 +411,40 @@ impl BoardWidget {
 
     pub fn reset(&mut self, puzzle: &Puzzle) {
         self.current_board = puzzle.board.clone();
+        if self.width != puzzle.board.width || self.height != puzzle.board.height {
+            self.width = puzzle.board.width;
+            self.height = puzzle.board.height;
+            self.initialize_drawables(self.board_area);
+        }
+
         self.state = GameState::SelectSource(None);
         self.reset_squares();
     }
 
+    /// Takes back the last move, returns false when there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        if self.current_board.unmake_move().is_none() {
+            return false;
+        }
+
+        self.state = GameState::SelectSource(None);
+        self.reset_squares();
+        true
+    }
+
     fn handle_select_source(
         &mut self,
         mouse_pos: Circle,
         previous_target: Option<(usize, usize)>,
     ) -> GameState {
         self.reset_squares();
+        let chained = self.chained_square();
         let mut selected = None;
         for square in &mut self.squares {
             if mouse_pos.overlaps_rect(&square.rect) {
-                if let Some(_) = self.current_board.cells[square.i][square.j] {
+                if let Some(_) = self.current_board.cells[square.i][square.j]
+                    && chained.is_none_or(|chained| chained == (square.i, square.j))
+                {
                     selected = Some((square.i, square.j));
                 }
             }
@@ -285,9 +470,22 @@ impl BoardWidget {
             self.get(i, j).is_previous_target = true;
         }
 
+        // Keep pointing at the only piece that can move on
+        if let Some((i, j)) = chained {
+            self.get(i, j).is_target = true;
+            return GameState::SelectSource(Some((i, j)));
+        }
+
         return GameState::SelectSource(None);
     }
 
+    /// With chain captures, the square of the piece that made the last
+    /// capture, as it is the only one that may move next
+    fn chained_square(&self) -> Option<(usize, usize)> {
+        let square = self.current_board.chained_square()?;
+        Some((square.file, square.rank))
+    }
+
     fn handle_select_target(
         &mut self,
         mouse_pos: Circle,
@@ -321,39 +519,71 @@ impl BoardWidget {
         }
 
         if is_legal {
-            let m = self.current_board.legal_moves.iter().find(|m| {
-                m.from.file == s_x && m.from.rank == s_y && m.to.file == x && m.to.rank == y
-            });
+            let moves = self.moves_between(source, (x, y));
+            if moves.len() > 1 {
+                self.reset_squares();
+                return GameState::SelectPromotion(source, (x, y));
+            }
 
+            let m = moves.into_iter().next();
             let m = m.expect("legal move should be found");
-            self.current_board.make_move(m.clone());
+            return self.play_move(m, resources, settings);
+        }
 
-            if self.current_board.game_state == BoardState::Won
-                || self.current_board.game_state == BoardState::Lost
-            {
-                self.reset_squares();
-                if self.current_board.game_state == BoardState::Won {
-                    play_sound_once(resources.sound(&SoundKind::Win), settings.volume);
-                } else {
-                    play_sound_once(resources.sound(&SoundKind::Loss), settings.volume);
-                }
+        self.reset_squares();
+        return GameState::SelectSource(None);
+    }
 
-                return GameState::GameOver((x, y));
-            }
+    fn handle_select_promotion(
+        &mut self,
+        mouse_pos: Circle,
+        source: (usize, usize),
+        target: (usize, usize),
+        resources: &Resources,
+        settings: &GameSettings,
+    ) -> GameState {
+        let moves = self.moves_between(source, target);
+        let rects = self.promotion_rects(moves.len());
+        let selected = moves
+            .into_iter()
+            .zip(rects)
+            .find(|(_, rect)| mouse_pos.overlaps_rect(rect));
+
+        // Clicking anywhere else takes the move back
+        let Some((m, _)) = selected else {
+            self.reset_squares();
+            return GameState::SelectSource(None);
+        };
+
+        self.play_move(m, resources, settings)
+    }
 
+    fn play_move(&mut self, m: CMove, resources: &Resources, settings: &GameSettings) -> GameState {
+        let (x, y) = (m.to.file, m.to.rank);
+        self.current_board.make_move(m);
+
+        if self.current_board.game_state == BoardState::Won
+            || self.current_board.game_state == BoardState::Lost
+        {
             self.reset_squares();
-            self.get(x, y).is_target = true;
-            play_sound_once(resources.sound(&SoundKind::Click), settings.volume);
-            return GameState::SelectSource(Some((x, y)));
+            if self.current_board.game_state == BoardState::Won {
+                play_sound_once(resources.sound(&SoundKind::Win), settings.volume);
+            } else {
+                play_sound_once(resources.sound(&SoundKind::Loss), settings.volume);
+            }
+
+            return GameState::GameOver((x, y));
         }
 
         self.reset_squares();
-        return GameState::SelectSource(None);
+        self.get(x, y).is_target = true;
+        play_sound_once(resources.sound(&SoundKind::Click), settings.volume);
+        return GameState::SelectSource(Some((x, y)));
     }
 
     fn reset_squares(&mut self) {
-        for i in 0..self.num_squares {
-            for j in 0..self.num_squares {
+        for i in 0..self.width {
+            for j in 0..self.height {
                 self.get(i, j).is_source = false;
                 self.get(i, j).is_target = false;
             }
@@ -375,6 +605,10 @@ struct GameSquare {
 pub enum GameState {
     SelectSource(Option<(usize, usize)>),
     SelectTarget((usize, usize)),
+
+    // A pawn moved from the first square to the second, waiting for the
+    // piece it promotes to
+    SelectPromotion((usize, usize), (usize, usize)),
     GameOver((usize, usize)),
 }
 
@@ -390,6 +624,11 @@ impl Display for GameState {
             GameState::SelectSource(Some(x)) => write!(f, "Select Source [ {}, {} ]", x.0, x.1),
             GameState::SelectSource(None) => write!(f, "Select Source [ ]"),
             GameState::SelectTarget(x) => write!(f, "Select Target [ {}, {} ]", x.0, x.1),
+            GameState::SelectPromotion(s, t) => write!(
+                f,
+                "Select Promotion [ {}, {} ] -> [ {}, {} ]",
+                s.0, s.1, t.0, t.1
+            ),
             GameState::GameOver(x) => write!(f, "Game Over [ {}, {} ]", x.0, x.1),
         }
     }
@@ -418,12 +657,72 @@ fn piece_draw_texture_params<'a>(
     }
 }
 
+/// Fairy pieces share their sprite with an orthodox piece, so their letter is
+/// drawn in the corner of the sprite at (x, y) to tell them apart
+fn draw_fairy_letter(piece: &Piece, x: f32, y: f32, sprite_size: f32, resources: &Resources) {
+    if !piece.kind.is_fairy() {
+        return;
+    }
+
+    let font_size = (sprite_size * 0.35) as u16;
+    draw_text_ex(
+        &piece.kind.notation(),
+        x + sprite_size * 0.7,
+        y + sprite_size * 0.35,
+        TextParams {
+            font_size,
+            font: Some(resources.font()),
+            color: UiColor::Brown.to_bg_color(),
+            ..Default::default()
+        },
+    );
+}
+
+/// Blocked squares are not part of the board, they are drawn as a hole with a
+/// cross over it
+fn draw_blocked_square(rect: &Rect) {
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, UiColor::Green.to_bg_color());
+
+    let inset = 0.25 * rect.w;
+    let thickness = 0.04 * rect.w;
+    let color = UiColor::Grey.to_bg_color();
+    draw_line(
+        rect.x + inset,
+        rect.y + inset,
+        rect.x + rect.w - inset,
+        rect.y + rect.h - inset,
+        thickness,
+        color,
+    );
+    draw_line(
+        rect.x + rect.w - inset,
+        rect.y + inset,
+        rect.x + inset,
+        rect.y + rect.h - inset,
+        thickness,
+        color,
+    );
+}
+
+/// The target piece stands on a ring, so that it can be told apart from the
+/// other pieces of its kind
+fn draw_target_piece_marker(rect: &Rect) {
+    draw_circle_lines(
+        rect.x + rect.w / 2.0,
+        rect.y + rect.h / 2.0,
+        0.45 * rect.w,
+        0.05 * rect.w,
+        UiColor::Blue.to_bg_color(),
+    );
+}
+
 struct PieceDrawTextureParams<'a> {
     texture: &'a Texture2D,
     draw_text_params: DrawTextureParams,
 }
 
-pub struct BoardDrawParams {
+pub struct BoardDrawParams<'a> {
     pub show_rules: bool,
     pub rules_font_size: f32,
+    pub rules_text: &'a str,
 }
@@ -1,7 +1,12 @@
+pub mod betza;
 pub mod cmove;
-mod constants;
+pub mod constants;
 pub mod errors;
+pub mod fen;
+pub mod id;
 pub mod piece;
+pub mod position;
+pub mod ruleset;
 pub mod square;
 
 use core::fmt;
@@ -9,25 +14,46 @@ use std::{
     collections::HashSet,
     fmt::{Display, Formatter},
     mem,
+    sync::Arc,
 };
 
 use cmove::CMove;
-use constants::BOARD_SIZE;
+use constants::{BLOCKED_SQUARE, BOARD_SIZE, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
 use errors::SError;
-use piece::PieceKind;
-use square::{Square, SquarePair};
-
-use crate::{board::piece::Piece, generator::Puzzle};
+use piece::{PieceDefinition, PieceKind};
+use position::{AttackTables, Position};
+use ruleset::Ruleset;
+use square::Square;
+
+use crate::{
+    board::piece::Piece,
+    generator::Puzzle,
+    solver::{
+        self, Solutions, Solver,
+        budget::{BudgetExhausted, SearchBudget},
+        difficulty::Difficulty,
+    },
+};
 
 #[derive(Clone, Default)]
 pub struct Board {
-    pub cells: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE],
+    pub cells: Vec<Vec<Option<Piece>>>,
     pub legal_moves: HashSet<CMove>,
     pub game_state: BoardState,
     pub id: String,
-    pub size: usize,
+    pub width: usize,
+    pub height: usize,
     pieces_remaining: u8,
-    max_moves_per_piece: u32,
+    rules: Ruleset,
+    tables: Arc<AttackTables>,
+
+    // Moves made since the pieces were last set, oldest first. Every move
+    // holds both pieces as they were before it was made.
+    history: Vec<CMove>,
+
+    // With chain captures, the square of the piece that captured last before
+    // the history starts, for boards that were set up in the middle of a chain
+    chain_start: Option<u8>,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -44,78 +70,150 @@ impl Default for BoardState {
     }
 }
 
+#[derive(Clone)]
 pub struct BoardOptions {
-    pub max_moves_per_piece: u32,
+    pub rules: Ruleset,
+    pub width: usize,
+    pub height: usize,
+
+    /// Squares that are cut out of the board, as a mask over the square
+    /// indices `rank * width + file`. Pieces can not stand on them and
+    /// sliders can not pass through them.
+    pub blocked: u64,
+
+    pub topology: Topology,
+
+    /// Pieces defined for the board on top of the built in ones, at most
+    /// `MAX_PIECE_DEFINITIONS` of them
+    pub piece_definitions: Vec<PieceDefinition>,
+}
+
+/// Which edges of the board wrap around to the opposite edge. Pieces move
+/// across a wrapping edge as if the board repeated beyond it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    #[default]
+    Flat,
+
+    /// The a file and the last file are next to each other
+    Cylinder,
+
+    /// Like the cylinder, and the top and bottom ranks are next to each other
+    Torus,
+}
+
+impl Topology {
+    pub fn wraps_files(&self) -> bool {
+        *self != Topology::Flat
+    }
+
+    pub fn wraps_ranks(&self) -> bool {
+        *self == Topology::Torus
+    }
+}
+
+impl Default for BoardOptions {
+    fn default() -> Self {
+        BoardOptions {
+            rules: Ruleset::default(),
+            width: BOARD_SIZE,
+            height: BOARD_SIZE,
+            blocked: 0,
+            topology: Topology::Flat,
+            piece_definitions: vec![],
+        }
+    }
 }
 
 impl Board {
     pub fn new() -> Self {
-        let options = BoardOptions {
-            max_moves_per_piece: 2,
-        };
-        Board::create(options)
+        Board::create(BoardOptions::default())
     }
 
     pub fn create(options: BoardOptions) -> Self {
-        let cells = [[None; BOARD_SIZE]; BOARD_SIZE];
-        let id = Board::encode(cells);
-        Board {
+        let size_range = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+        if !size_range.contains(&options.width) || !size_range.contains(&options.height) {
+            panic!(
+                "Board dimensions should be between {}x{} and {}x{}",
+                MIN_BOARD_SIZE, MIN_BOARD_SIZE, MAX_BOARD_SIZE, MAX_BOARD_SIZE
+            );
+        }
+
+        let cells = vec![vec![None; options.height]; options.width];
+        let mut board = Board {
             cells,
             legal_moves: HashSet::new(),
-            id,
+            id: String::new(),
             pieces_remaining: 0,
             game_state: BoardState::NotStarted,
-            size: BOARD_SIZE,
-            max_moves_per_piece: options.max_moves_per_piece,
-        }
+            width: options.width,
+            height: options.height,
+            rules: options.rules,
+            tables: AttackTables::new(
+                options.width,
+                options.height,
+                options.blocked,
+                options.topology,
+                &options.piece_definitions,
+            ),
+            history: Vec::new(),
+            chain_start: None,
+        };
+        board.calc_id();
+        board
     }
 
+    /// Decodes both v1 and v2 ids, see `id` for the formats
     pub fn from_id(board_id: &str) -> Result<Self, SError> {
-        // TODO: validate board_id before decode
-        let mut board_id_bytes = [0; 8];
-        board_id_bytes.copy_from_slice(board_id.as_bytes());
-        let mut working_bytes_slice = [0; 6];
-        b64_decode_exact_48(&board_id_bytes, &mut working_bytes_slice);
+        id::decode(board_id)
+    }
 
-        let mut working_bytes = [0; 8];
-        working_bytes[2..].copy_from_slice(&working_bytes_slice);
-        let mut working = u64::from_be_bytes(working_bytes);
+    /// Parses a FEN like position, see `fen` for the format
+    pub fn from_fen(fen: &str) -> Result<Self, SError> {
+        fen::decode(fen)
+    }
 
-        let mut board = Board::new();
-        let mask = 0b111;
-        for i in (0..BOARD_SIZE).rev() {
-            for j in (0..BOARD_SIZE).rev() {
-                let piece_kind = Board::get_piece_from_encoding((working & mask) as u8);
-                working = working >> 3;
-                let piece_kind = piece_kind?;
-                board.set(Square::new(i, j, Piece::from_kind(piece_kind)));
-            }
-        }
-        Ok(board)
+    /// The position as a FEN like string, including the age of every piece
+    /// and the age limit
+    pub fn to_fen(&self) -> String {
+        fen::encode(self)
     }
 
+    /// Board strings list the ranks from the top of the board, so the board is
+    /// assumed to be square and its size is inferred from the length. Blocked
+    /// squares are given by `#`.
     pub fn from_string(board_string: String) -> Result<Self, SError> {
-        if board_string.chars().count() != 16 {
-            return Err(SError::InvalidBoard);
-        }
+        let size = (MIN_BOARD_SIZE..=MAX_BOARD_SIZE)
+            .find(|size| size * size == board_string.chars().count())
+            .ok_or(SError::InvalidLength(board_string.chars().count()))?;
+
+        // Squares are listed in the order of their indices
+        let blocked = board_string
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| *c == BLOCKED_SQUARE)
+            .fold(0, |blocked, (sq, _)| blocked | 1 << sq);
+        let mut board = Board::create(BoardOptions {
+            width: size,
+            height: size,
+            blocked,
+            ..Default::default()
+        });
+        let mut chars = board_string.chars().enumerate();
+        for r in 0..size {
+            for f in 0..size {
+                let (position, c) = chars.next().unwrap();
+                if c == BLOCKED_SQUARE {
+                    continue;
+                }
 
-        let mut board = Board::new();
-        let mut chars = board_string.chars();
-        for r in 0..BOARD_SIZE {
-            for f in 0..BOARD_SIZE {
-                let c = chars.next().unwrap();
-                let piece_kind = match c {
-                    'K' | 'k' => PieceKind::King,
-                    'Q' | 'q' => PieceKind::Queen,
-                    'B' | 'b' => PieceKind::Bishop,
-                    'N' | 'n' => PieceKind::Knight,
-                    'R' | 'r' => PieceKind::Rook,
-                    'P' | 'p' => PieceKind::Pawn,
-                    '.' => continue,
-                    _ => return Err(SError::InvalidBoard),
+                let piece_kind = match PieceKind::parse(&c.to_ascii_uppercase().to_string()) {
+                    Ok(Some(kind)) => kind,
+                    Ok(None) => continue,
+                    Err(_) => return Err(SError::InvalidChar { position, found: c }),
                 };
 
-                let square = Square::new(f, r, Some(Piece::new(piece_kind)));
+                let square = Square::new(f, r, size, Some(Piece::new(piece_kind)));
                 board.set(square);
             }
         }
@@ -123,7 +221,17 @@ impl Board {
     }
 
     pub fn set(&mut self, square: Square) -> Option<Piece> {
+        if let Some(piece) = square.piece
+            && !self.tables.is_defined(piece.kind)
+        {
+            panic!("Trying to place a piece that is not defined for the board");
+        }
+
         let new_is_occuppied = square.piece.is_some();
+        if new_is_occuppied && self.is_blocked(square.file, square.rank) {
+            panic!("Trying to place a piece on a blocked square");
+        }
+
         let existing = mem::replace(&mut self.cells[square.file][square.rank], square.piece);
 
         // If placing a piece on a blank, increment piece count
@@ -136,284 +244,279 @@ impl Board {
             self.pieces_remaining -= 1;
         }
 
+        // Moves made before the change cannot be undone on top of it
+        self.history.clear();
+        self.chain_start = None;
         self.board_state_changed();
         existing
     }
 
     pub fn make_move(&mut self, mv: CMove) -> Option<CMove> {
-        if !self.legal_moves.contains(&mv) {
+        // Match on the squares and the promotion alone, the caller might hold
+        // a move built before the pieces involved had moved
+        let legal_move = self.legal_moves.iter().find(|m| {
+            m.from.file == mv.from.file
+                && m.from.rank == mv.from.rank
+                && m.to.file == mv.to.file
+                && m.to.rank == mv.to.rank
+                && m.promotion == mv.promotion
+        });
+        let Some(mv) = legal_move.cloned() else {
             println!("Invalid move - {}", mv.notation());
             println!("Legal moves - ");
             for m in &self.legal_moves {
                 println!("{}", m.notation());
             }
             return None;
-        }
+        };
 
-        let mut from_piece = mem::replace(&mut self.cells[mv.from.file][mv.from.rank], None);
+        let mut from_piece = self.cells[mv.from.file][mv.from.rank].take();
         if let Some(p) = &mut from_piece {
+            p.kind = mv
+                .promotion
+                .unwrap_or_else(|| self.rules.kind_after_capture(p.kind, mv.to_piece.kind));
             p.moves_made += 1;
-            if p.moves_made >= self.max_moves_per_piece {
-                p.active = false;
-            }
+            p.active = self.rules.can_move(p.moves_made);
         }
 
         self.cells[mv.to.file][mv.to.rank] = from_piece;
 
         self.pieces_remaining -= 1;
+        self.history.push(mv.clone());
         self.board_state_changed();
         Some(mv)
     }
 
+    /// Takes back the last move, putting the captured piece back and
+    /// restoring the age of the piece that moved.
+    pub fn unmake_move(&mut self) -> Option<CMove> {
+        let mv = self.history.pop()?;
+        self.cells[mv.from.file][mv.from.rank] = Some(mv.from_piece);
+        self.cells[mv.to.file][mv.to.rank] = Some(mv.to_piece);
+
+        self.pieces_remaining += 1;
+        self.board_state_changed();
+        Some(mv)
+    }
+
+    /// Takes back every move, returning to the board as it was last set up
+    pub fn undo_all(&mut self) {
+        while self.unmake_move().is_some() {}
+    }
+
+    /// The moves made so far, oldest first
+    pub fn history(&self) -> &[CMove] {
+        &self.history
+    }
+
     pub fn empty_squares(&self) -> Vec<Square> {
         let mut empty_squares = Vec::new();
-        for file in 0..BOARD_SIZE {
-            for rank in 0..BOARD_SIZE {
-                if self.cells[file][rank].is_none() {
-                    empty_squares.push(Square::new(file, rank, None));
+        for (file, ranks) in self.cells.iter().enumerate() {
+            for (rank, cell) in ranks.iter().enumerate() {
+                if cell.is_none() && !self.is_blocked(file, rank) {
+                    empty_squares.push(Square::new(file, rank, self.height, None));
                 }
             }
         }
         empty_squares
     }
 
+    /// Every square holding a piece, with the piece
+    pub fn occupied_squares(&self) -> Vec<Square> {
+        let mut occupied_squares = Vec::new();
+        for (file, ranks) in self.cells.iter().enumerate() {
+            for (rank, cell) in ranks.iter().enumerate() {
+                if cell.is_some() {
+                    occupied_squares.push(Square::new(file, rank, self.height, *cell));
+                }
+            }
+        }
+        occupied_squares
+    }
+
     pub fn pretty_print(&self) {
         println!("{}", self.print(true));
         // println!("{:^40}\n", format!("id: {:#018x}", self.id()));
-        println!("{:^40}\n", format!("id: {}", self.id));
+        println!("{:^40}", format!("id: {}", self.id));
+        println!("{:^40}\n", format!("fen: {}", self.to_fen()));
     }
 
     pub fn solve(&self) -> Puzzle {
-        struct StackItem {
-            board: Board,
-            moves_so_far: Vec<CMove>,
-            next_move: CMove,
-        }
-
-        if let BoardState::Won = self.game_state {
-            return Puzzle {
-                board: self.clone(),
-                solutions: vec![vec![]],
-                solved: true,
-            };
-        }
-
-        let mut stack = Vec::new();
-        for mv in &self.legal_moves {
-            let item = StackItem {
-                board: self.clone(),
-                moves_so_far: vec![],
-                next_move: mv.clone(),
-            };
+        solver::unbounded(Solver::new(self).solve(self))
+    }
 
-            stack.push(item);
-        }
+    /// Like `solve`, but gives up once `budget` is spent
+    pub fn solve_within(&self, budget: SearchBudget) -> Result<Puzzle, BudgetExhausted> {
+        Solver::with_budget(self, budget).solve(self)
+    }
 
-        let mut solutions = Vec::new();
-        loop {
-            let top = stack.pop();
-            let Some(top) = top else {
-                let solved = solutions.len() > 0;
-                return Puzzle {
-                    board: self.clone(),
-                    solutions,
-                    solved,
-                };
-            };
-
-            let (mut board, mut moves_so_far, next) = (top.board, top.moves_so_far, top.next_move);
-            board.make_move(next.clone());
-            match board.game_state {
-                BoardState::Won => {
-                    moves_so_far.push(next);
-                    solutions.push(moves_so_far);
-                }
-                BoardState::InProgress => {
-                    moves_so_far.push(next);
-                    for mv in &board.legal_moves {
-                        let item = StackItem {
-                            board: board.clone(),
-                            moves_so_far: moves_so_far.clone(),
-                            next_move: mv.clone(),
-                        };
-
-                        stack.push(item);
-                    }
-                }
-                _ => {}
-            }
-        }
+    /// Number of solutions to this board, without building every solution
+    pub fn solution_count(&self) -> u64 {
+        solver::unbounded(Solver::new(self).count(&self.position()))
     }
 
-    fn encode(cells: [[Option<Piece>; BOARD_SIZE]; BOARD_SIZE]) -> String {
-        let mut res: u64 = 0;
+    /// Number of solutions to this board, counting stops at `limit`
+    pub fn count_solutions(&self, limit: u64) -> u64 {
+        solver::unbounded(Solver::new(self).count_solutions(&self.position(), limit))
+    }
 
-        for i in 0..BOARD_SIZE {
-            for j in 0..BOARD_SIZE {
-                res = res << 3;
-                let byte = Board::get_piece_encoding(cells[i][j]);
-                res = res | byte as u64
-            }
-        }
+    /// How hard this board is to solve, see `difficulty`
+    pub fn difficulty(&self) -> Difficulty {
+        solver::unbounded(Solver::new(self).rate(self))
+    }
 
-        let mut id_bytes = [0; 6];
-        id_bytes.copy_from_slice(&res.to_be_bytes()[2..]);
-        b64_encode_exact_48(&id_bytes)
+    /// Like `difficulty`, but gives up once `budget` is spent
+    pub fn difficulty_within(&self, budget: SearchBudget) -> Result<Difficulty, BudgetExhausted> {
+        Solver::with_budget(self, budget).rate(self)
     }
 
-    fn print(&self, pretty: bool) -> String {
-        let mut board_string = String::new();
-        for rank in 0..BOARD_SIZE {
-            let mut row = String::new();
-            for file in 0..BOARD_SIZE {
-                let piece = self.cells[file][rank];
-                row.push_str(&get_square_for_display(&piece, pretty));
-            }
+    pub fn is_solvable(&self) -> bool {
+        solver::unbounded(Solver::new(self).is_solvable(&self.position()))
+    }
 
-            if pretty {
-                board_string.push_str(&format!("{:^40}\n", row));
-            } else {
-                board_string.push_str(&row);
-            }
+    pub fn solve_first(&self) -> Option<Vec<CMove>> {
+        solver::unbounded(Solver::new(self).solve_first(self))
+    }
 
-            board_string.push('\n');
-        }
+    /// Iterates over the solutions as the search finds them, without
+    /// holding on to the ones already returned.
+    pub fn solutions(&self) -> Solutions<'_> {
+        Solver::new(self).solutions(self)
+    }
 
-        board_string
+    /// The bitboard representation of this board, used by the solver
+    pub fn position(&self) -> Position {
+        Position::from_board(self, &self.tables)
     }
 
-    fn calc_legal_moves(&mut self) {
-        self.legal_moves = self
-            .all_possible_move_pairs()
-            .into_iter()
-            .filter(SquarePair::is_different)
-            .filter_map(|pair| self.is_legal_move(pair))
-            .collect()
+    pub fn rules(&self) -> &Ruleset {
+        &self.rules
     }
 
-    fn is_legal_move(&self, pair: SquarePair) -> Option<CMove> {
-        // The below block is just to make the compiler happy. Start will always
-        // have a piece
-        let Some(piece) = pair.start.piece else {
-            return None;
-        };
+    /// Changes the rules the pieces on the board are played by. Like `set`,
+    /// this starts a new game from the current position.
+    pub fn set_rules(&mut self, rules: Ruleset) {
+        for piece in self.cells.iter_mut().flatten().flatten() {
+            piece.active = rules.can_move(piece.moves_made);
+        }
+
+        self.rules = rules;
+        self.history.clear();
+        self.chain_start = None;
+        self.board_state_changed();
+    }
 
-        if piece.moves_made >= self.max_moves_per_piece {
+    /// With chain captures, the square of the piece that captured last, as
+    /// it is the only one that may move next
+    pub fn chained_square(&self) -> Option<Square> {
+        if !self.rules.chain_capture() {
             return None;
         }
 
-        let legal = match piece.kind {
-            PieceKind::King => self.is_king_legal(&pair),
-            PieceKind::Queen => self.is_queen_legal(&pair),
-            PieceKind::Bishop => self.is_bishop_legal(&pair),
-            PieceKind::Knight => self.is_knight_legal(&pair),
-            PieceKind::Rook => self.is_rook_legal(&pair),
-            PieceKind::Pawn => self.is_pawn_legal(&pair),
+        let (file, rank) = match self.history.last() {
+            Some(mv) => (mv.to.file, mv.to.rank),
+            None => self.tables.coordinates(self.chain_start? as usize),
         };
+        Some(Square::new(file, rank, self.height, self.cells[file][rank]))
+    }
 
-        if legal {
-            return Some(CMove::new(pair.start, pair.end));
+    /// Carries on a chain of captures made before the board was set up, so
+    /// that only the piece on (file, rank) may move next
+    pub(crate) fn continue_chain(&mut self, file: usize, rank: usize) -> Result<(), SError> {
+        if self.cells[file][rank].is_none() {
+            return Err(SError::InvalidSquare(
+                Square::new(file, rank, self.height, None).notation(),
+            ));
         }
 
-        None
+        self.chain_start = Some(self.tables.index(file, rank) as u8);
+        self.board_state_changed();
+        Ok(())
     }
 
-    fn is_king_legal(&self, pair: &SquarePair) -> bool {
-        pair.dx <= 1 && pair.dy <= 1
+    /// The square the last piece has to stand on, if the rules have one
+    pub fn target_square(&self) -> Option<Square> {
+        let sq = self.rules.target_square()? as usize;
+        let (file, rank) = self.tables.coordinates(sq);
+        Some(Square::new(file, rank, self.height, self.cells[file][rank]))
     }
 
-    fn is_queen_legal(&self, pair: &SquarePair) -> bool {
-        self.is_path_free(pair)
+    /// Mask of the blocked squares, see `BoardOptions::blocked`
+    pub fn blocked(&self) -> u64 {
+        self.tables.blocked
     }
 
-    fn is_bishop_legal(&self, pair: &SquarePair) -> bool {
-        pair.dx == pair.dy && self.is_path_free(pair)
+    pub fn topology(&self) -> Topology {
+        self.tables.topology
     }
 
-    fn is_knight_legal(&self, pair: &SquarePair) -> bool {
-        (pair.dx == 2 && pair.dy == 1) || (pair.dx == 1 && pair.dy == 2)
+    pub fn is_blocked(&self, file: usize, rank: usize) -> bool {
+        self.tables.blocked & (1 << self.tables.index(file, rank)) != 0
     }
 
-    fn is_rook_legal(&self, pair: &SquarePair) -> bool {
-        if pair.dx != 0 && pair.dy != 0 {
-            return false;
+    /// The options this board was created with, holding its current rules
+    pub fn options(&self) -> BoardOptions {
+        BoardOptions {
+            rules: self.rules.clone(),
+            width: self.width,
+            height: self.height,
+            blocked: self.blocked(),
+            topology: self.topology(),
+            piece_definitions: self.piece_definitions().to_vec(),
         }
-
-        self.is_path_free(pair)
     }
 
-    fn is_pawn_legal(&self, pair: &SquarePair) -> bool {
-        pair.dx == 1 && pair.dy == 1 && pair.y_dir == -1
+    pub fn max_moves_per_piece(&self) -> u32 {
+        self.rules.max_moves_per_piece
     }
 
-    fn is_path_free(&self, pair: &SquarePair) -> bool {
-        // There is no straight line or diagonal to get through
-        if pair.dx != pair.dy && pair.dx != 0 && pair.dy != 0 {
-            return false;
-        }
-
-        let x_inc = pair.x_dir;
-        let y_inc = pair.y_dir;
-        let mut x: i8 = pair.start.file.try_into().unwrap();
-        let mut y: i8 = pair.start.rank.try_into().unwrap();
+    /// Pieces defined for this board on top of the built in ones
+    pub fn piece_definitions(&self) -> &[PieceDefinition] {
+        &self.tables.definitions
+    }
 
-        loop {
-            x = x + x_inc;
-            y = y + y_inc;
+    pub(crate) fn tables(&self) -> Arc<AttackTables> {
+        self.tables.clone()
+    }
 
-            let file: usize = x.try_into().unwrap();
-            let rank: usize = y.try_into().unwrap();
-            if rank == pair.end.rank && file == pair.end.file {
-                return true;
+    fn print(&self, pretty: bool) -> String {
+        let mut board_string = String::new();
+        for rank in 0..self.height {
+            let mut row = String::new();
+            for file in 0..self.width {
+                let square = match self.is_blocked(file, rank) {
+                    true => get_blocked_square_for_display(pretty),
+                    false => get_square_for_display(&self.cells[file][rank], pretty),
+                };
+                row.push_str(&square);
             }
 
-            if self.cells[file][rank].is_some() {
-                return false;
+            if pretty {
+                board_string.push_str(&format!("{:^40}\n", row));
+            } else {
+                board_string.push_str(&row);
             }
-        }
-    }
 
-    fn calc_game_state(&mut self) {
-        self.game_state = if self.pieces_remaining == 0 {
-            BoardState::NotStarted
-        } else if self.pieces_remaining == 1 {
-            BoardState::Won
-        } else if self.legal_moves.is_empty() {
-            BoardState::Lost
-        } else {
-            BoardState::InProgress
+            board_string.push('\n');
         }
+
+        board_string
     }
 
-    /// This is just a cartesian product of {occupied_squares} x {occupied_squares}
-    fn all_possible_move_pairs(&self) -> impl IntoIterator<Item = SquarePair> {
-        let ret = self
-            .all_occupied_squares()
+    fn calc_legal_moves(&mut self) {
+        let position = self.position();
+        let mut moves = Vec::new();
+        position.legal_moves(&self.tables, &self.rules, &mut moves);
+        self.legal_moves = moves
             .into_iter()
-            .map(|start| {
-                self.all_occupied_squares()
-                    .into_iter()
-                    .map(move |end| SquarePair::new(start.clone(), end))
-            })
-            .flatten()
-            .collect::<Vec<SquarePair>>();
-
-        return ret;
+            .map(|mv| position.to_cmove(mv, &self.tables, &self.rules))
+            .collect()
     }
 
-    fn all_occupied_squares(&self) -> impl IntoIterator<Item = Square> {
-        let mut ret = Vec::new();
-
-        for i in 0..BOARD_SIZE {
-            for j in 0..BOARD_SIZE {
-                let p = &self.cells[i][j];
-                if p.is_some() {
-                    ret.push(Square::new(i, j, *p))
-                }
-            }
-        }
-
-        ret
+    fn calc_game_state(&mut self) {
+        self.game_state = self
+            .rules
+            .game_state(&self.position(), !self.legal_moves.is_empty());
     }
 
     fn board_state_changed(&mut self) {
@@ -422,35 +525,8 @@ impl Board {
         self.calc_id();
     }
 
-    fn get_piece_encoding(piece: Option<Piece>) -> u8 {
-        match piece {
-            Some(p) => match p.kind {
-                PieceKind::King => 0b001,
-                PieceKind::Queen => 0b010,
-                PieceKind::Rook => 0b011,
-                PieceKind::Bishop => 0b100,
-                PieceKind::Knight => 0b101,
-                PieceKind::Pawn => 0b110,
-            },
-            None => 0b000,
-        }
-    }
-
-    fn get_piece_from_encoding(encoding: u8) -> Result<Option<PieceKind>, SError> {
-        match encoding {
-            0b001 => Ok(Some(PieceKind::King)),
-            0b010 => Ok(Some(PieceKind::Queen)),
-            0b011 => Ok(Some(PieceKind::Rook)),
-            0b100 => Ok(Some(PieceKind::Bishop)),
-            0b101 => Ok(Some(PieceKind::Knight)),
-            0b110 => Ok(Some(PieceKind::Pawn)),
-            0b000 => Ok(None),
-            _ => Err(SError::InvalidBoard),
-        }
-    }
-
     fn calc_id(&mut self) {
-        self.id = Board::encode(self.cells);
+        self.id = id::encode(self);
     }
 }
 
@@ -472,6 +548,13 @@ fn get_square_for_display(piece: &Option<Piece>, pretty: bool) -> String {
     }
 }
 
+fn get_blocked_square_for_display(pretty: bool) -> String {
+    match pretty {
+        true => format!(" {} ", BLOCKED_SQUARE),
+        false => BLOCKED_SQUARE.to_string(),
+    }
+}
+
 impl Display for BoardState {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let display = match self {
@@ -485,52 +568,13 @@ impl Display for BoardState {
     }
 }
 
-fn b64_encode_exact_48(input: &[u8; 6]) -> String {
-    let mut output = [0 as char; 8];
-    for (byte_chunk, output_slice) in input.chunks_exact(3).zip(output.chunks_exact_mut(4)) {
-        let byte1 = byte_chunk[0];
-        let byte2 = byte_chunk[1];
-        let byte3 = byte_chunk[2];
-
-        output_slice[0] = lookup((byte1 & 0b1111_1100) >> 2);
-        output_slice[1] = lookup((byte1 & 0b0000_0011) << 4 | (byte2 & 0b1111_0000) >> 4);
-        output_slice[2] = lookup((byte2 & 0b0000_1111) << 2 | (byte3 & 0b1100_0000) >> 6);
-        output_slice[3] = lookup(byte3 & 0b0011_1111);
-    }
-
-    output.iter().collect()
-}
-
-fn b64_decode_exact_48(input: &[u8; 8], output: &mut [u8; 6]) {
-    for (char_chunk, output_slice) in input.chunks_exact(4).zip(output.chunks_exact_mut(3)) {
-        let char_1 = reverse_lookup(char_chunk[0] as char);
-        let char_2 = reverse_lookup(char_chunk[1] as char);
-        let char_3 = reverse_lookup(char_chunk[2] as char);
-        let char_4 = reverse_lookup(char_chunk[3] as char);
-
-        output_slice[0] = (char_1 << 2) | (char_2 >> 4);
-        output_slice[1] = (char_2 << 4) | (char_3 >> 2);
-        output_slice[2] = (char_3 << 6) | char_4;
-    }
-}
-
-const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
-
-fn lookup(idx: u8) -> char {
-    ALPHABET.chars().nth(idx as usize).unwrap()
-}
-
-fn reverse_lookup(c: char) -> u8 {
-    ALPHABET.chars().position(|x| x == c).unwrap() as u8
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     macro_rules! sq {
         ($sq:literal) => {
-            Square::parse($sq)
+            Square::parse($sq, 4, 4).unwrap()
         };
     }
 
@@ -745,9 +789,53 @@ mod tests {
         validate_board!(board2, "..NB", "....", "RQ.K", "P...");
     }
 
+    #[test]
+    fn test_encoding_larger_board() {
+        let mut board = Board::create(BoardOptions {
+            width: 5,
+            height: 5,
+            ..Default::default()
+        });
+        board.set(Square::parse("Pa1", 5, 5).unwrap());
+        board.set(Square::parse("Re5", 5, 5).unwrap());
+        board.set(Square::parse("Nc3", 5, 5).unwrap());
+
+        let board2 = Board::from_id(&board.id).unwrap();
+        assert_eq!(5, board2.width);
+        assert_eq!(5, board2.height);
+        assert_eq!(board.print(false), board2.print(false));
+        assert_eq!("....R\n.....\n..N..\n.....\nP....\n", board2.print(false));
+    }
+
+    #[test]
+    fn test_legal_moves_large_board() {
+        let mut board = Board::create(BoardOptions {
+            width: 8,
+            height: 8,
+            ..Default::default()
+        });
+        let sq = |notation| Square::parse(notation, 8, 8).unwrap();
+        board.set(sq("Ra1"));
+        board.set(sq("Ph8"));
+        board.set(sq("Bh1"));
+        board.set(sq("Na8"));
+
+        let mv = |from, to| CMove::new(sq(from), sq(to));
+        validate_legal_moves!(board, mv("Ra1", "Na8"), mv("Ra1", "Bh1"), mv("Bh1", "Na8"),);
+    }
+
+    #[test]
+    fn test_board_string_sizes() {
+        let board = Board::from_string("n......r.".to_string()).unwrap();
+        assert_eq!(3, board.width);
+        assert_eq!(3, board.height);
+        assert_eq!(1, board.legal_moves.len());
+        assert!(Board::from_string("n.......".to_string()).is_err());
+    }
+
     macro_rules! sq {
         ($sq:literal) => {
-            Square::parse($sq)
+            Square::parse($sq, 4, 4).unwrap()
         };
     }
 
@@ -799,4 +887,106 @@ mod tests {
 
         assert_eq!(0, solutions.len());
     }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Err(SError::InvalidLength(7)),
+            Board::from_id("wmgYAAA").map(|_| ())
+        );
+        assert_eq!(
+            Err(SError::InvalidLength(0)),
+            Board::from_id("").map(|_| ())
+        );
+        assert_eq!(
+            Err(SError::InvalidChar {
+                position: 3,
+                found: '!'
+            }),
+            Board::from_id("wmg!AAAG").map(|_| ())
+        );
+
+        // '_' decodes to two cells with the unused code 0b111
+        assert_eq!(
+            Err(SError::InvalidPieceCode {
+                position: 2,
+                code: 0b111
+            }),
+            Board::from_id("A_AAAAAA").map(|_| ())
+        );
+
+        assert_eq!(
+            Err(SError::InvalidLength(5)),
+            Board::from_string("pb...".to_string()).map(|_| ())
+        );
+        assert_eq!(
+            Err(SError::InvalidChar {
+                position: 4,
+                found: 'x'
+            }),
+            Board::from_string("pb..x......r..p.".to_string()).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_unmake_move() {
+        let mut board = Board::from_string("pb......br..p..p".to_string()).unwrap();
+        let start = board.clone();
+        assert!(board.unmake_move().is_none());
+
+        let first = board.make_move(mv!("Rb2", "Bb4")).unwrap();
+        let second = board.make_move(mv!("Rb4", "Pa4")).unwrap();
+        assert_eq!(vec![first, second.clone()], board.history());
+        assert_eq!(2, board.cells[0][0].unwrap().moves_made);
+        assert!(!board.cells[0][0].unwrap().active);
+
+        assert_eq!(Some(second), board.unmake_move());
+        assert_eq!(1, board.history().len());
+        let rook = board.cells[1][0].unwrap();
+        assert_eq!(1, rook.moves_made);
+        assert!(rook.active);
+        assert_eq!(Some(PieceKind::Pawn), board.cells[0][0].map(|p| p.kind));
+
+        board.undo_all();
+        assert!(board.history().is_empty());
+        assert_eq!(start.cells, board.cells);
+        assert_eq!(start.id, board.id);
+        assert!(start.legal_moves == board.legal_moves);
+        assert_eq!(start.game_state, board.game_state);
+        assert_eq!(start.solution_count(), board.solution_count());
+    }
+
+    #[test]
+    fn test_unmake_move_after_game_over() {
+        let mut board = Board::from_string("n......r.".to_string()).unwrap();
+        let mv = board.legal_moves.iter().next().unwrap().clone();
+        board.make_move(mv);
+        assert_eq!(BoardState::Won, board.game_state);
+
+        board.unmake_move();
+        assert_eq!(BoardState::InProgress, board.game_state);
+        assert_eq!(1, board.legal_moves.len());
+
+        // Setting a piece starts a new history
+        board.make_move(board.legal_moves.iter().next().unwrap().clone());
+        board.set(Square::new(0, 0, 3, Some(Piece::new(PieceKind::Rook))));
+        assert!(board.history().is_empty());
+        assert!(board.unmake_move().is_none());
+    }
+
+    #[test]
+    fn test_blocked_squares() {
+        let board = Board::from_string("r.#.....#......r".to_string()).unwrap();
+        assert!(board.is_blocked(2, 0));
+        assert!(board.is_blocked(0, 2));
+        assert_eq!(12, board.empty_squares().len());
+        assert_eq!("R.#.\n....\n#...\n...R\n", board.print(false));
+
+        // The rooks on a4 and d4 see each other until c4 is blocked
+        let board = Board::from_string("r..r............".to_string()).unwrap();
+        assert_eq!(2, board.legal_moves.len());
+        let board = Board::from_string("r.#r............".to_string()).unwrap();
+        assert_eq!(0, board.legal_moves.len());
+        assert_eq!(BoardState::Lost, board.game_state);
+    }
 }
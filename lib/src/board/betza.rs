@@ -0,0 +1,222 @@
+//! Piece movement in Betza notation, for example `BN` for the archbishop or
+//! `NN` for the nightrider.
+//!
+//! Supported are the leaper atoms `W F D N A H C Z G`, the compounds `K`
+//! (`WF`), `R` (`WW`), `B` (`FF`) and `Q` (`RB`), a doubled atom for an
+//! unlimited rider and a number after an atom for a rider of limited range,
+//! like `R2`. Atoms can be preceded by the direction modifiers `f b l r v s`.
+//! Every move is a capture, so `c` is accepted and ignored and atoms with an
+//! `m` only make non capturing moves and are left out.
+
+use super::errors::SError;
+
+/// (file, rank) step. Ranks are counted from the top of the board, so a
+/// negative rank step moves forward.
+pub type Step = (i8, i8);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveRule {
+    /// Jumps straight to the square `step` away
+    Leap(Step),
+
+    /// Repeats `step` until the first piece, at most `range` times if set
+    Ride { step: Step, range: Option<u8> },
+}
+
+/// Parses a Betza string into the move rules of a piece
+pub fn parse(betza: &str) -> Result<Vec<MoveRule>, SError> {
+    let invalid = |reason: &str| SError::InvalidBetza(format!("{}: {}", reason, betza));
+    let mut rules = vec![];
+    let mut chars = betza.chars().peekable();
+    while chars.peek().is_some() {
+        let mut modifiers = String::new();
+        while let Some(modifier) = chars.next_if(char::is_ascii_lowercase) {
+            modifiers.push(modifier);
+        }
+
+        let atom = chars.next().ok_or_else(|| invalid("Missing atom"))?;
+        let (atoms, rider) = match atom {
+            'K' => (vec!['W', 'F'], false),
+            'R' => (vec!['W'], true),
+            'B' => (vec!['F'], true),
+            'Q' => (vec!['W', 'F'], true),
+            _ => (vec![atom], false),
+        };
+
+        // A doubled leaper is its rider
+        let rider = rider || (atom_step(atom).is_some() && chars.next_if_eq(&atom).is_some());
+        let mut range = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            range.push(digit);
+        }
+        let range = match range.as_str() {
+            "" if rider => None,
+            "" => Some(1),
+            range => Some(range.parse().map_err(|_| invalid("Invalid range"))?),
+        };
+        if range == Some(0) {
+            return Err(invalid("Invalid range"));
+        }
+
+        let mut steps = vec![];
+        for atom in atoms {
+            let (long, short) =
+                atom_step(atom).ok_or_else(|| invalid(&format!("Unknown atom '{}'", atom)))?;
+            for step in symmetric_steps(long, short) {
+                if !steps.contains(&step) {
+                    steps.push(step);
+                }
+            }
+        }
+
+        let mut non_capturing = false;
+        let (mut vertical, mut horizontal, mut orientation) = (vec![], vec![], vec![]);
+        for modifier in modifiers.chars() {
+            match modifier {
+                'f' | 'b' => vertical.push(modifier),
+                'l' | 'r' => horizontal.push(modifier),
+                'v' | 's' => orientation.push(modifier),
+                'c' => {}
+                'm' => non_capturing = true,
+                _ => return Err(invalid(&format!("Unsupported modifier '{}'", modifier))),
+            }
+        }
+
+        if non_capturing && !modifiers.contains('c') {
+            continue;
+        }
+
+        let allowed = |modifiers: &[char], (dx, dy): Step| {
+            modifiers.is_empty()
+                || modifiers.iter().any(|modifier| match modifier {
+                    'f' => dy < 0,
+                    'b' => dy > 0,
+                    'l' => dx < 0,
+                    'r' => dx > 0,
+                    'v' => dy.abs() > dx.abs(),
+                    _ => dx.abs() > dy.abs(),
+                })
+        };
+        steps.retain(|step| {
+            allowed(&vertical, *step) && allowed(&horizontal, *step) && allowed(&orientation, *step)
+        });
+        if steps.is_empty() {
+            return Err(invalid("Modifiers leave no moves"));
+        }
+
+        rules.extend(steps.into_iter().map(|step| match range {
+            Some(1) => MoveRule::Leap(step),
+            range => MoveRule::Ride { step, range },
+        }));
+    }
+
+    if rules.is_empty() {
+        return Err(invalid("No captures"));
+    }
+
+    Ok(rules)
+}
+
+/// The (long, short) leg of the leap of an atom
+fn atom_step(atom: char) -> Option<(i8, i8)> {
+    match atom {
+        'W' => Some((1, 0)),
+        'F' => Some((1, 1)),
+        'D' => Some((2, 0)),
+        'N' => Some((2, 1)),
+        'A' => Some((2, 2)),
+        'H' => Some((3, 0)),
+        'C' => Some((3, 1)),
+        'Z' => Some((3, 2)),
+        'G' => Some((3, 3)),
+        _ => None,
+    }
+}
+
+/// Every rotation and reflection of a leap, without repeats
+fn symmetric_steps(long: i8, short: i8) -> Vec<Step> {
+    let mut steps = vec![];
+    for (dx, dy) in [(long, short), (short, long)] {
+        for (sx, sy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let step = (dx * sx, dy * sy);
+            if !steps.contains(&step) {
+                steps.push(step);
+            }
+        }
+    }
+
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaps(betza: &str) -> Vec<Step> {
+        let mut leaps = parse(betza)
+            .unwrap()
+            .into_iter()
+            .map(|rule| match rule {
+                MoveRule::Leap(step) => step,
+                rider => panic!("expected only leaps, found {:?}", rider),
+            })
+            .collect::<Vec<_>>();
+        leaps.sort();
+        leaps
+    }
+
+    #[test]
+    fn test_parse_leapers() {
+        assert_eq!(8, leaps("N").len());
+        assert_eq!(8, leaps("K").len());
+        assert_eq!(4, leaps("W").len());
+        assert_eq!(vec![(-3, -1), (-3, 1), (-1, -3), (-1, 3)], leaps("lC"));
+        assert_eq!(vec![(-1, -1), (1, -1)], leaps("fF"));
+        assert_eq!(vec![(-1, -1), (1, -1)], leaps("fcF"));
+        assert_eq!(vec![(0, -1)], leaps("fW"));
+        assert_eq!(vec![(-1, -2), (1, -2)], leaps("fvN"));
+        assert_eq!(leaps("WF"), leaps("K"));
+        assert_eq!(leaps("F"), leaps("mWF"));
+    }
+
+    #[test]
+    fn test_parse_riders() {
+        let rules = parse("NN").unwrap();
+        assert_eq!(8, rules.len());
+        assert!(rules.contains(&MoveRule::Ride {
+            step: (2, 1),
+            range: None
+        }));
+
+        let rules = parse("R2").unwrap();
+        assert_eq!(4, rules.len());
+        assert!(rules.contains(&MoveRule::Ride {
+            step: (0, -1),
+            range: Some(2)
+        }));
+
+        // The archbishop rides diagonally and leaps like a knight
+        let rules = parse("BN").unwrap();
+        assert_eq!(12, rules.len());
+        assert_eq!(
+            4,
+            rules
+                .iter()
+                .filter(|rule| matches!(rule, MoveRule::Ride { range: None, .. }))
+                .count()
+        );
+        assert_eq!(parse("Q").unwrap().len(), parse("RB").unwrap().len());
+        assert_eq!(parse("W").unwrap(), parse("W1").unwrap());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for betza in ["", "X", "f", "Rx", "jN", "W0", "mW", "flW"] {
+            assert!(
+                matches!(parse(betza), Err(SError::InvalidBetza(_))),
+                "expected invalid betza: {}",
+                betza
+            );
+        }
+    }
+}
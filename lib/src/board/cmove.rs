@@ -1,16 +1,21 @@
+use std::hash::{Hash, Hasher};
+
 use crate::board::piece::Piece;
 
-use super::{piece::PieceKind, square::Square};
+use super::{Board, constants::MAX_BOARD_SIZE, errors::SError, piece::PieceKind, square::Square};
 
-#[derive(PartialEq, Hash, Eq, Clone)]
+#[derive(Clone, Debug)]
 pub struct CMove {
     pub from_piece: Piece,
     pub from: Square,
     pub to_piece: Piece,
     pub to: Square,
 
+    /// The piece a pawn capturing onto the top rank turns into
+    pub promotion: Option<PieceKind>,
+
     // Used to disambiguate when looking at notation
-    disambig: String,
+    pub(crate) disambig: String,
 }
 
 impl CMove {
@@ -23,20 +28,291 @@ impl CMove {
             from,
             to_piece,
             to,
+            promotion: None,
             disambig,
         }
     }
 
+    /// Resolves a capture in short algebraic notation against the legal moves
+    /// of `board`. Accepts what `notation` prints, like `RdxBb4` or `bxPa4=Q`,
+    /// as well as the shorter `Rdxb4`, `bxa3` or `Rb4`.
+    pub fn parse(board: &Board, notation: &str) -> Result<Self, SError> {
+        let invalid = || SError::InvalidMove(notation.to_string());
+        let parse_piece = |piece| PieceKind::parse_with(piece, board.piece_definitions());
+
+        // Read from the end: the promotion, the target square, the captured
+        // piece and `x`
+        let rest = notation.trim();
+        let (rest, promotion) = match rest.split_once('=') {
+            Some((rest, promotion)) => match parse_piece(promotion) {
+                Ok(Some(promotion)) => (rest, Some(promotion)),
+                _ => return Err(invalid()),
+            },
+            None => (rest, None),
+        };
+        let (rest, to_rank) = strip_rank(rest);
+        let (rest, to_file) = strip_file(rest);
+        let (Some(to_rank), Some(to_file)) = (to_rank, to_file) else {
+            return Err(invalid());
+        };
+
+        let (rest, captured) = match rest.strip_suffix(|c: char| c.is_ascii_uppercase()) {
+            Some(before) if before.ends_with('x') => (
+                before,
+                parse_piece(&rest[before.len()..]).map_err(|_| invalid())?,
+            ),
+            _ => (rest, None),
+        };
+        let rest = rest.strip_suffix('x').unwrap_or(rest);
+
+        // What is left is the moving piece and the square it moves from, of
+        // which pawns only give the file
+        let (rest, kind) = match rest.chars().next() {
+            Some(c) if c.is_ascii_uppercase() => (
+                &rest[1..],
+                parse_piece(&c.to_string()).map_err(|_| invalid())?,
+            ),
+            _ => (rest, Some(PieceKind::Pawn)),
+        };
+        let (rest, from_rank) = strip_rank(rest);
+        let (rest, from_file) = strip_file(rest);
+        let Some(kind) = kind.filter(|_| rest.is_empty()) else {
+            return Err(invalid());
+        };
+
+        let rank_number = |square: &Square| square.height - square.rank;
+        let matches = board
+            .legal_moves
+            .iter()
+            .filter(|mv| {
+                mv.from_piece.kind == kind
+                    && mv.to.file == to_file
+                    && rank_number(&mv.to) == to_rank
+                    && captured.is_none_or(|captured| mv.to_piece.kind == captured)
+                    && from_file.is_none_or(|file| mv.from.file == file)
+                    && from_rank.is_none_or(|rank| rank_number(&mv.from) == rank)
+                    && promotion.is_none_or(|promotion| mv.promotion == Some(promotion))
+            })
+            .collect::<Vec<_>>();
+
+        match matches.as_slice() {
+            [mv] => Ok((*mv).clone()),
+            [] => Err(SError::IllegalMove(notation.to_string())),
+            _ => Err(SError::AmbiguousMove(notation.to_string())),
+        }
+    }
+
     pub fn notation(&self) -> String {
         let piece_qualifier = match &self.from_piece.kind {
             PieceKind::Pawn => self.from.file_notation(),
             p => p.notation(),
         };
+        let promotion = match self.promotion {
+            Some(kind) => format!("={}", kind.notation()),
+            None => String::new(),
+        };
         format!(
-            "{}{}x{}",
+            "{}{}x{}{}",
             piece_qualifier,
             self.disambig,
-            self.to.notation()
+            self.to.notation(),
+            promotion
         )
     }
 }
+
+// The disambiguation only depends on the other pieces on the board, so it is
+// left out when comparing moves
+impl PartialEq for CMove {
+    fn eq(&self, other: &Self) -> bool {
+        self.from_piece == other.from_piece
+            && self.from == other.from
+            && self.to_piece == other.to_piece
+            && self.to == other.to
+            && self.promotion == other.promotion
+    }
+}
+
+impl Eq for CMove {}
+
+impl Hash for CMove {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.from_piece.hash(state);
+        self.from.hash(state);
+        self.to_piece.hash(state);
+        self.to.hash(state);
+        self.promotion.hash(state);
+    }
+}
+
+/// Splits a trailing rank number off `notation`
+fn strip_rank(notation: &str) -> (&str, Option<usize>) {
+    let start = notation
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .len();
+    let rank = notation[start..].parse().ok();
+    (&notation[..start], rank)
+}
+
+/// Splits a trailing file letter off `notation`
+fn strip_file(notation: &str) -> (&str, Option<usize>) {
+    match notation.chars().last() {
+        Some(c) if ('a'..).take(MAX_BOARD_SIZE).any(|file| file == c) => (
+            &notation[..notation.len() - 1],
+            Some(c as usize - 'a' as usize),
+        ),
+        _ => (notation, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_string(board_string: &str) -> Board {
+        Board::from_string(board_string.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        // P B . .
+        // . . . .
+        // B R . .
+        // P . . P
+        let board = from_string("pb......br..p..p");
+        for notation in ["RxBb4", "Rxb4", "Rb4", "Rbxb4", "R2xb4", "Rb2xBb4"] {
+            let mv = CMove::parse(&board, notation).unwrap();
+            assert_eq!("RxBb4", mv.notation());
+        }
+
+        let mv = CMove::parse(&board, "axb2").unwrap();
+        assert_eq!(PieceKind::Pawn, mv.from_piece.kind);
+        assert_eq!("axRb2", mv.notation());
+
+        assert_eq!(
+            Err(SError::IllegalMove("Rxa4".to_string())),
+            CMove::parse(&board, "Rxa4")
+        );
+        assert_eq!(
+            Err(SError::IllegalMove("RxNb4".to_string())),
+            CMove::parse(&board, "RxNb4")
+        );
+        for notation in ["", "R", "Rx", "Rxb", "Xxb4", "RxXb4", "R?xb4", "Rxz4"] {
+            assert_eq!(
+                Err(SError::InvalidMove(notation.to_string())),
+                CMove::parse(&board, notation)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_pawn() {
+        // . . . .
+        // . B . .
+        // P . P .
+        // . . . .
+        let board = from_string(".....b..p.p.....");
+        assert_eq!("axBb3", CMove::parse(&board, "axb3").unwrap().notation());
+        assert_eq!("cxBb3", CMove::parse(&board, "cxBb3").unwrap().notation());
+        assert_eq!(
+            Err(SError::AmbiguousMove("xb3".to_string())),
+            CMove::parse(&board, "xb3")
+        );
+    }
+
+    #[test]
+    fn test_disambiguation() {
+        // R . . R
+        // . . . .
+        // . . . .
+        // R . . .
+        let board = from_string("r..r........r...");
+        let notations = |board: &Board| {
+            let mut notations = board
+                .legal_moves
+                .iter()
+                .map(|mv| mv.notation())
+                .collect::<Vec<_>>();
+            notations.sort();
+            notations
+        };
+        assert_eq!(
+            vec!["RaxRa4", "RdxRa4", "RxRa1", "RxRd4"],
+            notations(&board)
+        );
+        assert_eq!(
+            Err(SError::AmbiguousMove("Rxa4".to_string())),
+            CMove::parse(&board, "Rxa4")
+        );
+        assert_eq!("RaxRa4", CMove::parse(&board, "R1xa4").unwrap().notation());
+        assert_eq!("RdxRa4", CMove::parse(&board, "Rdxa4").unwrap().notation());
+
+        // . . . .
+        // Q . . .
+        // . P . .
+        // Q . Q .
+        // The queens on a3 and c1 share a file and a rank with the one on a1
+        let board = from_string("....q....p..q.q.");
+        assert!(notations(&board).contains(&"Qa1xPb2".to_string()));
+        assert_eq!(
+            "Qa1xPb2",
+            CMove::parse(&board, "Qa1xb2").unwrap().notation()
+        );
+    }
+
+    #[test]
+    fn test_parse_replays_solution() {
+        let board = from_string("r..r.n....b.r.qr");
+        for solution in board.solutions().take(50) {
+            let mut replay = board.clone();
+            for mv in solution {
+                let parsed = CMove::parse(&replay, &mv.notation()).unwrap();
+                assert!(replay.make_move(parsed).is_some());
+            }
+            assert_eq!(1, replay.position().piece_count());
+        }
+    }
+
+    #[test]
+    fn test_parse_promotion() {
+        let board = Board::from_fen("N3/1P2/4/3R 2 promote").unwrap();
+        let mv = CMove::parse(&board, "bxNa4=Q").unwrap();
+        assert_eq!(Some(PieceKind::Queen), mv.promotion);
+        assert_eq!("bxNa4=Q", mv.notation());
+        assert_eq!(
+            "bxNa4=N",
+            CMove::parse(&board, "bxa4=N").unwrap().notation()
+        );
+
+        // Without the piece the move could be any of the promotions
+        assert_eq!(
+            Err(SError::AmbiguousMove("bxa4".to_string())),
+            CMove::parse(&board, "bxa4")
+        );
+        assert_eq!(
+            Err(SError::IllegalMove("bxa4=K".to_string())),
+            CMove::parse(&board, "bxa4=K")
+        );
+        assert_eq!(
+            Err(SError::InvalidMove("bxa4=".to_string())),
+            CMove::parse(&board, "bxa4=")
+        );
+
+        let mut board = board;
+        board.make_move(mv);
+        assert_eq!(Some(PieceKind::Queen), board.cells[0][0].map(|p| p.kind));
+        assert_eq!("QxRd1", board.legal_moves.iter().next().unwrap().notation());
+        board.unmake_move();
+        assert_eq!(Some(PieceKind::Pawn), board.cells[1][1].map(|p| p.kind));
+    }
+
+    #[test]
+    fn test_parse_chameleon() {
+        // The rook taking the knight on b4 goes on as a knight
+        let mut board = Board::from_fen("RN2/3P/4/4 2 chameleon").unwrap();
+        board.make_move(CMove::parse(&board, "RxNb4").unwrap());
+        let mv = CMove::parse(&board, "Nxd3").unwrap();
+        assert_eq!("NxPd3", mv.notation());
+        assert_eq!(PieceKind::Knight, mv.from_piece.kind);
+    }
+}
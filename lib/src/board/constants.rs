@@ -1 +1,14 @@
+// Dimensions used by `Board::new`
 pub const BOARD_SIZE: usize = 4;
+
+// Boards are limited to 8x8 so that every square fits into a u64 mask
+pub const MIN_BOARD_SIZE: usize = 1;
+pub const MAX_BOARD_SIZE: usize = 8;
+
+// Pieces that can be defined at runtime on top of the built in ones, and the
+// length of the Betza notation of each
+pub const MAX_PIECE_DEFINITIONS: usize = 4;
+pub const MAX_BETZA_LEN: usize = 31;
+
+// Stands for a blocked square in board strings and FEN like strings
+pub const BLOCKED_SQUARE: char = '#';
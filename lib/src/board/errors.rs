@@ -1,4 +1,67 @@
-#[derive(Debug)]
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SError {
-    InvalidBoard,
+    /// The id or board string does not have the length of any supported board
+    InvalidLength(usize),
+
+    /// A character that is not allowed at this position, counted in
+    /// characters from the start of the input
+    InvalidChar { position: usize, found: char },
+
+    /// A square that could not be parsed, like `Kz9`
+    InvalidSquare(String),
+
+    /// A piece letter that does not name a piece
+    InvalidPiece(String),
+
+    /// An id decoded to a piece code that is not assigned to any piece. The
+    /// position is the index of the cell, file by file.
+    InvalidPieceCode { position: usize, code: u8 },
+
+    /// A v2 id with flags that this version does not know about
+    UnsupportedFlags(u8),
+
+    /// A move that could not be parsed
+    InvalidMove(String),
+
+    /// A move that no legal move on the board matches
+    IllegalMove(String),
+
+    /// A move that more than one legal move on the board matches
+    AmbiguousMove(String),
+
+    /// A game record that could not be parsed
+    InvalidRecord(String),
+
+    /// A FEN position string that could not be parsed
+    InvalidFen(String),
+
+    /// A piece movement in Betza notation that could not be parsed
+    InvalidBetza(String),
 }
+
+impl Display for SError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SError::InvalidLength(len) => write!(f, "Invalid length {}", len),
+            SError::InvalidChar { position, found } => {
+                write!(f, "Invalid character '{}' at position {}", found, position)
+            }
+            SError::InvalidSquare(square) => write!(f, "Invalid square '{}'", square),
+            SError::InvalidPiece(piece) => write!(f, "Invalid piece '{}'", piece),
+            SError::InvalidPieceCode { position, code } => {
+                write!(f, "Invalid piece code {:#05b} in cell {}", code, position)
+            }
+            SError::UnsupportedFlags(flags) => write!(f, "Unsupported id flags {:#08b}", flags),
+            SError::InvalidMove(mv) => write!(f, "Invalid move '{}'", mv),
+            SError::IllegalMove(mv) => write!(f, "Illegal move '{}'", mv),
+            SError::AmbiguousMove(mv) => write!(f, "Ambiguous move '{}'", mv),
+            SError::InvalidRecord(reason) => write!(f, "Invalid game record: {}", reason),
+            SError::InvalidFen(reason) => write!(f, "Invalid FEN: {}", reason),
+            SError::InvalidBetza(reason) => write!(f, "Invalid Betza notation: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SError {}
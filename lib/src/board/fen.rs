@@ -0,0 +1,501 @@
+//! FEN like position strings, for example `PB2/4/BR[1]2/P2P 2`.
+//!
+//! The ranks are listed from the top of the board and separated by `/`. Every
+//! piece is given by its letter, followed by the number of moves it has made
+//! in brackets unless it has not moved yet and by `*` if it is the target
+//! piece, runs of empty squares by their length and blocked squares by `#`.
+//! The rules fields after the position hold the age limit, which can be left
+//! out for the default limit, followed by the definition of every piece that
+//! is not built in, like `piece=X:WD` for the piece `X` capturing as the Betza
+//! notation `WD` says, and every rule variant in play, like `solo` for Solo
+//! Chess, `promote` or `promote=Q` for pawns that promote by choice or always
+//! to the same piece, `chameleon` for pieces that turn into the piece they
+//! capture, `chain` for games where only the piece that captured last may move
+//! on, written `chain=c3` with the square of that piece once the chain has
+//! started, `cylinder` or `torus` for boards whose edges wrap around or
+//! `target=c3` for the square the last piece has to stand on.
+
+use super::{
+    Board, BoardOptions, Topology,
+    constants::{BLOCKED_SQUARE, MAX_BOARD_SIZE, MAX_PIECE_DEFINITIONS, MIN_BOARD_SIZE},
+    errors::SError,
+    piece::{Piece, PieceDefinition, PieceKind},
+    ruleset::{ChainCapture, Chameleon, Promotion, RoyalKing, Ruleset, TargetSquare, Variant},
+    square::Square,
+};
+
+pub(crate) fn encode(board: &Board) -> String {
+    let mut ranks = vec![];
+    for rank in 0..board.height {
+        let mut fen_rank = String::new();
+        let mut empty = 0;
+        for file in 0..board.width {
+            let blocked = board.is_blocked(file, rank);
+            let piece = board.cells[file][rank];
+            if piece.is_none() && !blocked {
+                empty += 1;
+                continue;
+            }
+
+            if empty > 0 {
+                fen_rank.push_str(&empty.to_string());
+                empty = 0;
+            }
+
+            let Some(piece) = piece else {
+                fen_rank.push(BLOCKED_SQUARE);
+                continue;
+            };
+
+            fen_rank.push_str(&piece.kind.notation());
+            if piece.moves_made > 0 {
+                fen_rank.push_str(&format!("[{}]", piece.moves_made));
+            }
+
+            if piece.target {
+                fen_rank.push('*');
+            }
+        }
+
+        if empty > 0 {
+            fen_rank.push_str(&empty.to_string());
+        }
+
+        ranks.push(fen_rank);
+    }
+
+    let mut fen = format!("{} {}", ranks.join("/"), board.max_moves_per_piece());
+    for definition in board.piece_definitions() {
+        fen.push_str(&format!(" piece={}", definition.notation()));
+    }
+
+    if board.rules().royal_king() {
+        fen.push_str(" solo");
+    }
+
+    if let Some(promotion) = board.rules().promotion() {
+        fen.push_str(&format!(" {}", promotion.name()));
+    }
+
+    if board.rules().chameleon() {
+        fen.push_str(" chameleon");
+    }
+
+    match board.chained_square() {
+        Some(square) => fen.push_str(&format!(" chain={}", square_notation(&square))),
+        None if board.rules().variant::<ChainCapture>().is_some() => fen.push_str(" chain"),
+        None => {}
+    }
+
+    match board.topology() {
+        Topology::Flat => {}
+        Topology::Cylinder => fen.push_str(" cylinder"),
+        Topology::Torus => fen.push_str(" torus"),
+    }
+
+    if let Some(target) = board.target_square() {
+        fen.push_str(&format!(" target={}", square_notation(&target)));
+    }
+
+    fen
+}
+
+fn square_notation(square: &Square) -> String {
+    square.file_notation() + &square.rank_notation()
+}
+
+pub(crate) fn decode(fen: &str) -> Result<Board, SError> {
+    let invalid = |reason: &str| SError::InvalidFen(format!("{}: {}", reason, fen));
+    let mut fields = fen.split_whitespace();
+    let placement = fields.next().ok_or_else(|| invalid("Missing position"))?;
+    let max_moves_per_piece = match fields.next() {
+        Some(rules) => rules.parse().map_err(|_| invalid("Invalid age limit"))?,
+        None => Ruleset::default().max_moves_per_piece,
+    };
+    let mut rules = Ruleset::with_age_limit(max_moves_per_piece);
+    let mut target_square = None;
+    let mut chained_square = None;
+    let mut promotion = None;
+    let mut topology = Topology::Flat;
+    let mut piece_definitions: Vec<PieceDefinition> = vec![];
+    for variant in fields {
+        match variant.split_once('=') {
+            None if variant == "solo" => rules.add(RoyalKing),
+            None if variant == "promote" => rules.add(Promotion::Choice),
+            None if variant == "chameleon" => rules.add(Chameleon),
+            None if variant == "chain" => rules.add(ChainCapture),
+            None if variant == "cylinder" => topology = Topology::Cylinder,
+            None if variant == "torus" => topology = Topology::Torus,
+            Some(("promote", kind)) => promotion = Some(kind),
+            Some(("piece", definition)) => {
+                let definition = PieceDefinition::parse(definition)
+                    .map_err(|_| invalid("Invalid piece definition"))?;
+                if piece_definitions.len() == MAX_PIECE_DEFINITIONS {
+                    return Err(invalid("Too many piece definitions"));
+                }
+                if piece_definitions
+                    .iter()
+                    .any(|other| other.letter == definition.letter)
+                {
+                    return Err(invalid("Piece defined twice"));
+                }
+                piece_definitions.push(definition);
+            }
+            Some(("chain", square)) => {
+                rules.add(ChainCapture);
+                chained_square = Some(square);
+            }
+            Some(("target", square)) => target_square = Some(square),
+            _ => return Err(invalid("Unknown rule variant")),
+        }
+    }
+
+    // Pawns can promote to any piece defined in the fields, and to anything
+    // but the one king in Solo Chess
+    if let Some(kind) = promotion {
+        match PieceKind::parse_with(kind, &piece_definitions) {
+            Ok(Some(PieceKind::King)) if rules.royal_king() => {
+                return Err(invalid("Invalid promotion"));
+            }
+            Ok(Some(kind)) => rules.add(Promotion::To(kind)),
+            _ => return Err(invalid("Invalid promotion")),
+        }
+    }
+
+    // (file, rank, piece) of every piece, (file, rank) of every blocked square
+    // and the width of every rank
+    let mut pieces = vec![];
+    let mut blocked_squares = vec![];
+    let mut widths = vec![0];
+    let mut chars = placement.chars().enumerate().peekable();
+    while let Some((position, c)) = chars.next() {
+        let rank = widths.len() - 1;
+        let file = &mut widths[rank];
+        match c {
+            '/' => widths.push(0),
+            '1'..='9' => *file += c as usize - '0' as usize,
+            BLOCKED_SQUARE => {
+                blocked_squares.push((*file, rank));
+                *file += 1;
+            }
+            _ if c.is_ascii_alphabetic() => {
+                let kind =
+                    PieceKind::parse_with(&c.to_ascii_uppercase().to_string(), &piece_definitions)
+                        .ok()
+                        .flatten()
+                        .ok_or(SError::InvalidChar { position, found: c })?;
+
+                let mut moves_made = 0;
+                if chars.next_if(|(_, c)| *c == '[').is_some() {
+                    let mut age = String::new();
+                    while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                        age.push(digit);
+                    }
+
+                    if chars.next_if(|(_, c)| *c == ']').is_none() {
+                        return Err(invalid("Unterminated age"));
+                    }
+                    moves_made = age.parse().map_err(|_| invalid("Invalid age"))?;
+                }
+
+                let target = chars.next_if(|(_, c)| *c == '*').is_some();
+                let piece = Piece {
+                    kind,
+                    moves_made,
+                    active: rules.can_move(moves_made),
+                    target,
+                };
+                pieces.push((*file, rank, piece));
+                *file += 1;
+            }
+            found => return Err(SError::InvalidChar { position, found }),
+        }
+    }
+
+    let width = widths[0];
+    let height = widths.len();
+    if widths.iter().any(|w| *w != width) {
+        return Err(invalid("Ranks of different widths"));
+    }
+
+    let size_range = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+    if !size_range.contains(&width) || !size_range.contains(&height) {
+        return Err(invalid("Unsupported board size"));
+    }
+
+    if pieces.iter().filter(|(_, _, piece)| piece.target).count() > 1 {
+        return Err(invalid("More than one target piece"));
+    }
+
+    let blocked = blocked_squares.iter().fold(0, |blocked, (file, rank)| {
+        blocked | 1 << (rank * width + file)
+    });
+    let parse_square = |square: &str| {
+        Square::parse(&format!(".{}", square), width, height)
+            .ok()
+            .filter(|square| blocked & 1 << (square.rank * width + square.file) == 0)
+    };
+    if let Some(square) = target_square {
+        let square = parse_square(square).ok_or_else(|| invalid("Invalid target square"))?;
+        rules.add(TargetSquare((square.rank * width + square.file) as u8));
+    }
+
+    let mut board = Board::create(BoardOptions {
+        rules,
+        width,
+        height,
+        blocked,
+        topology,
+        piece_definitions,
+    });
+    for (file, rank, piece) in pieces {
+        board.set(Square::new(file, rank, height, Some(piece)));
+    }
+
+    if let Some(square) = chained_square {
+        parse_square(square)
+            .and_then(|square| board.continue_chain(square.file, square.rank).ok())
+            .ok_or_else(|| invalid("Invalid chained square"))?;
+    }
+
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BoardState, cmove::CMove};
+
+    #[test]
+    fn test_fen_encode() {
+        let mut board = Board::from_string("pb......br..p..p".to_string()).unwrap();
+        assert_eq!("PB2/4/BR2/P2P 2", encode(&board));
+
+        board.make_move(CMove::parse(&board, "Rxb4").unwrap());
+        assert_eq!("PR[1]2/4/B3/P2P 2", encode(&board));
+
+        let board = Board::create(BoardOptions {
+            rules: Ruleset::with_age_limit(7),
+            width: 5,
+            height: 3,
+            ..Default::default()
+        });
+        assert_eq!("5/5/5 7", encode(&board));
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let mut board = Board::from_fen("QR[1]1N/1p[2]2/4/K2b 5").unwrap();
+        assert_eq!(4, board.width);
+        assert_eq!(4, board.height);
+        assert_eq!(5, board.max_moves_per_piece());
+        assert_eq!(Some(2), board.cells[1][1].map(|p| p.moves_made));
+        assert_eq!("QR[1]1N/1P[2]2/4/K2B 5", board.to_fen());
+
+        board.make_move(board.legal_moves.iter().next().unwrap().clone());
+        let decoded = Board::from_fen(&board.to_fen()).unwrap();
+        assert_eq!(board.cells, decoded.cells);
+        assert_eq!(board.id, decoded.id);
+        assert!(board.legal_moves == decoded.legal_moves);
+        assert_eq!(board.solution_count(), decoded.solution_count());
+    }
+
+    #[test]
+    fn test_fen_mid_game() {
+        // Only the rook on a1 has moves left
+        let board = Board::from_fen("4/4/4/R[1]P[2]2 2").unwrap();
+        assert_eq!(BoardState::InProgress, board.game_state);
+        assert_eq!(1, board.legal_moves.len());
+        assert_eq!(1, board.solution_count());
+
+        let board = Board::from_fen("4/4/4/R[2]P[2]2 2").unwrap();
+        assert_eq!(BoardState::Lost, board.game_state);
+
+        // The default age limit applies without a rules field
+        let board = Board::from_fen("2/2").unwrap();
+        assert_eq!(2, board.max_moves_per_piece());
+        assert_eq!(2, board.width);
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        let invalid = |fen: &str| match Board::from_fen(fen) {
+            Err(SError::InvalidFen(_)) => {}
+            _ => panic!("expected an invalid fen: {}", fen),
+        };
+        invalid("");
+        invalid("4/4/3 2");
+        invalid("4/4 two");
+        invalid("4/4 2 2");
+        invalid("9/9");
+        invalid("P[1/3");
+        invalid("P[]3/4");
+        invalid("4/4/4/4/4/4/4/4/4");
+
+        assert_eq!(
+            Err(SError::InvalidChar {
+                position: 3,
+                found: 'X'
+            }),
+            Board::from_fen("PB1X/4").map(|_| ())
+        );
+        assert_eq!(
+            Err(SError::InvalidChar {
+                position: 1,
+                found: '.'
+            }),
+            Board::from_fen("P.2/4").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_fen_piece_definitions() {
+        // X leaps one or two squares orthogonally, so the pieces on a4 and a2
+        // capture each other and the knight on d1 is out of reach
+        let fen = "X3/4/X3/3N 2 piece=X:WD";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(fen, board.to_fen());
+        assert_eq!(
+            &[PieceDefinition::new('X', "WD").unwrap()],
+            board.piece_definitions()
+        );
+        assert_eq!(2, board.legal_moves.len());
+        assert!(CMove::parse(&board, "XxXa2").is_ok());
+
+        // Pawns can promote to a defined piece
+        let mut board = Board::from_fen("X3/1P2/4/4 2 piece=X:W promote=X").unwrap();
+        assert_eq!(
+            Some(Promotion::To(PieceKind::Custom('X'))),
+            board.rules().promotion()
+        );
+        board.make_move(CMove::parse(&board, "bxXa4=X").unwrap());
+        assert_eq!(BoardState::Won, board.game_state);
+        assert_eq!("X[1]3/4/4/4 2 piece=X:W promote=X", board.to_fen());
+
+        let invalid = |fen: &str| match Board::from_fen(fen) {
+            Err(SError::InvalidFen(_)) | Err(SError::InvalidChar { .. }) => {}
+            _ => panic!("expected an invalid fen: {}", fen),
+        };
+        invalid("Y3/4/4/4 2 piece=X:W");
+        invalid("4/4/4/4 2 piece=N:W");
+        invalid("4/4/4/4 2 piece=X:W piece=X:D");
+        invalid("4/4/4/4 2 piece=X:J");
+        invalid("4/4/4/4 2 piece=D:D piece=E:E piece=F:F piece=G:G piece=I:W");
+        invalid("4/1P2/4/4 2 piece=X:W promote=Y");
+    }
+
+    #[test]
+    fn test_fen_rules() {
+        let board = Board::from_fen("4/4/1R2/K1N1 3 solo").unwrap();
+        assert!(board.rules().royal_king());
+        assert_eq!(3, board.max_moves_per_piece());
+        assert_eq!("4/4/1R2/K1N1 3 solo", board.to_fen());
+
+        // Neither the rook nor the knight may take the king
+        assert!(
+            board
+                .legal_moves
+                .iter()
+                .all(|mv| mv.to_piece.kind != PieceKind::King)
+        );
+    }
+
+    #[test]
+    fn test_fen_targets() {
+        let fen = "1N*1/R[1]2/3 2 target=c3";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(fen, board.to_fen());
+        assert!(board.cells[1][0].unwrap().target);
+        assert_eq!(Some(2), board.rules().target_square());
+
+        let invalid = |fen: &str| match Board::from_fen(fen) {
+            Err(SError::InvalidFen(_)) => {}
+            _ => panic!("expected an invalid fen: {}", fen),
+        };
+        invalid("N*N*/2 2");
+        invalid("2/2 2 target=c1");
+        invalid("2/2 2 target=a3");
+        invalid("2/2 2 target");
+        invalid("2/2 2 goal=a1");
+    }
+
+    #[test]
+    fn test_fen_blocked_squares() {
+        let fen = "#R1#/4/2#1/N2# 2";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(fen, board.to_fen());
+        assert!(board.is_blocked(0, 0));
+        assert!(board.is_blocked(2, 2));
+        assert!(!board.is_blocked(1, 0));
+        assert_eq!(4, board.blocked().count_ones());
+        assert_eq!(10, board.empty_squares().len());
+
+        let invalid = |fen: &str| match Board::from_fen(fen) {
+            Err(SError::InvalidFen(_)) => {}
+            _ => panic!("expected an invalid fen: {}", fen),
+        };
+        invalid("#4/4 2");
+        invalid("#1/2 2 target=a2");
+    }
+
+    #[test]
+    fn test_fen_promotion() {
+        for fen in [
+            "N3/1P2/4/3R 2 promote",
+            "N3/1P2/4/3R 2 solo promote=A",
+            "N3/1P2/4/3R 2 promote chameleon",
+            "N3/1P2/4/3R 2 chameleon chain",
+        ] {
+            assert_eq!(fen, Board::from_fen(fen).unwrap().to_fen());
+        }
+
+        let board = Board::from_fen("N3/1P2/4/3R 2 promote=Q").unwrap();
+        assert_eq!(
+            Some(Promotion::To(PieceKind::Queen)),
+            board.rules().promotion()
+        );
+        assert!(matches!(
+            Board::from_fen("4/4 2 promote=X"),
+            Err(SError::InvalidFen(_))
+        ));
+        assert!(Board::from_fen("K3/1P2/4/3R 2 promote=K").is_ok());
+        assert!(matches!(
+            Board::from_fen("K3/1P2/4/3R 2 solo promote=K"),
+            Err(SError::InvalidFen(_))
+        ));
+    }
+
+    #[test]
+    fn test_fen_topology() {
+        for fen in ["R2N/4/4/4 2 cylinder", "R3/#3/4/N3 3 chain torus target=a1"] {
+            assert_eq!(fen, Board::from_fen(fen).unwrap().to_fen());
+        }
+
+        let board = Board::from_fen("R2N/4/4/4 2").unwrap();
+        assert_eq!(Topology::Flat, board.topology());
+        let board = Board::from_fen("R2N/4/4/4 2 torus").unwrap();
+        assert_eq!(Topology::Torus, board.topology());
+    }
+
+    #[test]
+    fn test_fen_chained_square() {
+        // The rook on d4 has captured, so the bishop on a1 has to wait
+        let mut board = Board::from_fen("R2N/4/4/B2P 3 chain").unwrap();
+        board.make_move(CMove::parse(&board, "RxNd4").unwrap());
+        let fen = board.to_fen();
+        assert_eq!("3R[1]/4/4/B2P 3 chain=d4", fen);
+
+        let decoded = Board::from_fen(&fen).unwrap();
+        assert_eq!(fen, decoded.to_fen());
+        assert!(board.legal_moves == decoded.legal_moves);
+        assert_eq!(1, decoded.legal_moves.len());
+        assert_eq!(board.solution_count(), decoded.solution_count());
+
+        let invalid = |fen: &str| match Board::from_fen(fen) {
+            Err(SError::InvalidFen(_)) => {}
+            _ => panic!("expected an invalid fen: {}", fen),
+        };
+        invalid("3R/4/4/B2P 3 chain=c4");
+        invalid("3R/4/4/B2P 3 chain=e4");
+    }
+}
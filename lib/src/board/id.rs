@@ -0,0 +1,760 @@
+//! Puzzle ids are strings over a url safe base64 alphabet.
+//!
+//! v1 ids are exactly 8 characters: the 3 bit piece code of every cell of a
+//! 4x4 board, file by file, with every piece unmoved and an age limit of 2.
+//!
+//! v2 ids start with the version character `C` followed by a bit stream of
+//!
+//! - board width - 1 and height - 1, 3 bits each
+//! - the age limit, 8 bits
+//! - 6 bits of flags: the lowest is set when the board has fairy pieces,
+//!   which widens the piece codes to 4 bits, the next one when the board is
+//!   played by the Solo Chess rules, then one each for a target square and a
+//!   target piece, one for more rule variants and one for pieces defined for
+//!   the board, which widens the piece codes to 5 bits
+//! - the index `rank * width + file` of the target square and then of the
+//!   square of the target piece, 6 bits each, for the flags that are set
+//! - with the piece definitions flag, the number of definitions - 1 in 2 bits
+//!   and for each the offset of its letter from `A` in 5 bits, the length of
+//!   its Betza notation in 5 bits and every character of it as the 6 bit
+//!   index of the character in the id alphabet. The pieces get the codes
+//!   `0b10000` onwards in the order they are defined.
+//! - with the rule variants flag, 8 bits of rule variants: the lowest is set
+//!   when pawns promote by choice, the next one when they always promote to
+//!   the same piece, given by its piece code right after, then one for
+//!   chameleon captures, one for chain captures, one for a cylinder board,
+//!   one for a torus board and one for a chain of captures under way, with
+//!   the 6 bit index of the square of the piece that captured last right
+//!   after the promotion piece
+//! - the piece code of every cell, file by file, each occupied cell followed
+//!   by the age of its piece in just enough bits to hold the limit. Blocked
+//!   squares have the code `0b111`, which v1 ids never use.
+//!
+//! padded with zero bits to a whole character. Rule variants other than the
+//! ones in `ruleset` are not carried by ids. A v2 id that would be 8
+//! characters long gets one more padding character so that it is never taken
+//! for a v1 id. Boards that v1 can represent are always given v1 ids.
+
+use super::{
+    Board, BoardOptions, Topology,
+    constants::BOARD_SIZE,
+    errors::SError,
+    piece::{Piece, PieceDefinition, PieceKind},
+    ruleset::{ChainCapture, Chameleon, Promotion, RoyalKing, Ruleset, TargetSquare},
+    square::Square,
+};
+
+const V1_LEN: usize = 8;
+const V1_MAX_MOVES_PER_PIECE: u32 = 2;
+const V2: u8 = 2;
+const FLAG_FAIRY_PIECES: u32 = 0b000001;
+const FLAG_ROYAL_KING: u32 = 0b000010;
+const FLAG_TARGET_SQUARE: u32 = 0b000100;
+const FLAG_TARGET_PIECE: u32 = 0b001000;
+const FLAG_RULE_VARIANTS: u32 = 0b010000;
+const FLAG_PIECE_DEFINITIONS: u32 = 0b100000;
+const RULE_PROMOTION_CHOICE: u32 = 0b00000001;
+const RULE_PROMOTION_FIXED: u32 = 0b00000010;
+const RULE_CHAMELEON: u32 = 0b00000100;
+const RULE_CHAIN_CAPTURE: u32 = 0b00001000;
+const RULE_CYLINDER: u32 = 0b00010000;
+const RULE_TORUS: u32 = 0b00100000;
+const RULE_CHAINED_SQUARE: u32 = 0b01000000;
+const KNOWN_RULES: u32 = RULE_PROMOTION_CHOICE
+    | RULE_PROMOTION_FIXED
+    | RULE_CHAMELEON
+    | RULE_CHAIN_CAPTURE
+    | RULE_CYLINDER
+    | RULE_TORUS
+    | RULE_CHAINED_SQUARE;
+const BLOCKED_CODE: u8 = 0b111;
+const FIRST_DEFINED_CODE: u8 = 0b10000;
+const KNOWN_FLAGS: u32 = FLAG_FAIRY_PIECES
+    | FLAG_ROYAL_KING
+    | FLAG_TARGET_SQUARE
+    | FLAG_TARGET_PIECE
+    | FLAG_RULE_VARIANTS
+    | FLAG_PIECE_DEFINITIONS;
+
+const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub(crate) fn encode(board: &Board) -> String {
+    if is_v1(board) {
+        encode_v1(board)
+    } else {
+        encode_v2(board)
+    }
+}
+
+pub(crate) fn decode(id: &str) -> Result<Board, SError> {
+    let sextets = b64_decode(id)?;
+    if sextets.len() == V1_LEN {
+        return decode_v1(&sextets);
+    }
+
+    // Anything else that is not a v2 id is a v1 id of the wrong length
+    match sextets.first() {
+        Some(&V2) => decode_v2(&sextets[1..], sextets.len()),
+        _ => Err(SError::InvalidLength(sextets.len())),
+    }
+}
+
+fn is_v1(board: &Board) -> bool {
+    board.width == BOARD_SIZE
+        && board.height == BOARD_SIZE
+        && board.blocked() == 0
+        && board.topology() == Topology::Flat
+        && *board.rules() == Ruleset::with_age_limit(V1_MAX_MOVES_PER_PIECE)
+        && board.piece_definitions().is_empty()
+        && board
+            .cells
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|piece| piece.moves_made == 0 && !piece.kind.is_fairy() && !piece.target)
+}
+
+fn has_fairy_pieces(board: &Board) -> bool {
+    board
+        .cells
+        .iter()
+        .flatten()
+        .flatten()
+        .any(|piece| piece.kind.is_fairy())
+}
+
+fn encode_v1(board: &Board) -> String {
+    let mut writer = BitWriter::default();
+    for cell in board.cells.iter().flatten() {
+        writer.write(piece_code(cell.map(|piece| piece.kind), &[]) as u32, 3);
+    }
+
+    writer.finish()
+}
+
+fn decode_v1(sextets: &[u8]) -> Result<Board, SError> {
+    let mut board = Board::new();
+    let mut reader = BitReader::new(sextets, V1_LEN);
+    for file in 0..BOARD_SIZE {
+        for rank in 0..BOARD_SIZE {
+            let code = reader.read(3)? as u8;
+            let kind = decode_piece(code, file * BOARD_SIZE + rank, &[])?;
+            board.set(Square::new(file, rank, BOARD_SIZE, Piece::from_kind(kind)));
+        }
+    }
+
+    Ok(board)
+}
+
+fn encode_v2(board: &Board) -> String {
+    // Limits that do not fit are clamped, no piece ever makes that many moves
+    let max_moves_per_piece = board.max_moves_per_piece().min(u8::MAX as u32);
+    let age_bits = age_bits(max_moves_per_piece);
+    let definitions = board.piece_definitions();
+    let (mut flags, code_bits) = match (definitions.is_empty(), has_fairy_pieces(board)) {
+        (false, _) => (FLAG_PIECE_DEFINITIONS, 5),
+        (true, true) => (FLAG_FAIRY_PIECES, 4),
+        (true, false) => (0, 3),
+    };
+    if board.rules().royal_king() {
+        flags |= FLAG_ROYAL_KING;
+    }
+    let target_square = board.rules().target_square();
+    if target_square.is_some() {
+        flags |= FLAG_TARGET_SQUARE;
+    }
+    let target_piece = board.position().target();
+    if target_piece != 0 {
+        flags |= FLAG_TARGET_PIECE;
+    }
+    let (mut rules, promotion) = match board.rules().promotion() {
+        None => (0, None),
+        Some(Promotion::To(kind)) => (RULE_PROMOTION_FIXED, Some(kind)),
+        Some(Promotion::Choice) => (RULE_PROMOTION_CHOICE, None),
+    };
+    if board.rules().chameleon() {
+        rules |= RULE_CHAMELEON;
+    }
+    if board.rules().variant::<ChainCapture>().is_some() {
+        rules |= RULE_CHAIN_CAPTURE;
+    }
+    let chained_square = board.chained_square();
+    if chained_square.is_some() {
+        rules |= RULE_CHAINED_SQUARE;
+    }
+    match board.topology() {
+        Topology::Flat => {}
+        Topology::Cylinder => rules |= RULE_CYLINDER,
+        Topology::Torus => rules |= RULE_TORUS,
+    }
+    if rules != 0 {
+        flags |= FLAG_RULE_VARIANTS;
+    }
+
+    let mut writer = BitWriter::default();
+    writer.write(V2 as u32, 6);
+    writer.write(board.width as u32 - 1, 3);
+    writer.write(board.height as u32 - 1, 3);
+    writer.write(max_moves_per_piece, 8);
+    writer.write(flags, 6);
+    if let Some(sq) = target_square {
+        writer.write(sq as u32, 6);
+    }
+    if target_piece != 0 {
+        writer.write(target_piece.trailing_zeros(), 6);
+    }
+    if !definitions.is_empty() {
+        writer.write(definitions.len() as u32 - 1, 2);
+        for definition in definitions {
+            writer.write((definition.letter as u8 - b'A') as u32, 5);
+            writer.write(definition.betza.len() as u32, 5);
+            for c in definition.betza.chars() {
+                let c = reverse_lookup(c).expect("Betza notation is alphanumeric");
+                writer.write(c as u32, 6);
+            }
+        }
+    }
+    if rules != 0 {
+        writer.write(rules, 8);
+    }
+    if let Some(kind) = promotion {
+        writer.write(piece_code(Some(kind), definitions) as u32, code_bits.max(4));
+    }
+    if let Some(square) = chained_square {
+        writer.write((square.rank * board.width + square.file) as u32, 6);
+    }
+
+    for (file, ranks) in board.cells.iter().enumerate() {
+        for (rank, cell) in ranks.iter().enumerate() {
+            if board.is_blocked(file, rank) {
+                writer.write(BLOCKED_CODE as u32, code_bits);
+                continue;
+            }
+
+            let code = piece_code(cell.map(|piece| piece.kind), definitions);
+            writer.write(code as u32, code_bits);
+            if let Some(piece) = cell {
+                writer.write(piece.moves_made.min(max_moves_per_piece), age_bits);
+            }
+        }
+    }
+
+    let mut id = writer.finish();
+    if id.len() == V1_LEN {
+        id.push(lookup(0));
+    }
+
+    id
+}
+
+fn decode_v2(sextets: &[u8], id_len: usize) -> Result<Board, SError> {
+    let mut reader = BitReader::new(sextets, id_len);
+    let width = reader.read(3)? as usize + 1;
+    let height = reader.read(3)? as usize + 1;
+    let max_moves_per_piece = reader.read(8)?;
+    let flags = reader.read(6)?;
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(SError::UnsupportedFlags(flags as u8));
+    }
+    let code_bits = match (flags & FLAG_PIECE_DEFINITIONS, flags & FLAG_FAIRY_PIECES) {
+        (0, 0) => 3,
+        (0, _) => 4,
+        _ => 5,
+    };
+
+    let age_bits = age_bits(max_moves_per_piece);
+    let mut read_square = |flag| match flags & flag {
+        0 => Ok(None),
+        _ => match reader.read(6)? as usize {
+            sq if sq < width * height => Ok(Some(sq)),
+            sq => Err(SError::InvalidSquare(sq.to_string())),
+        },
+    };
+    let target_square = read_square(FLAG_TARGET_SQUARE)?;
+    let target_piece = read_square(FLAG_TARGET_PIECE)?;
+    let piece_definitions = match flags & FLAG_PIECE_DEFINITIONS {
+        0 => vec![],
+        _ => read_piece_definitions(&mut reader)?,
+    };
+    let variants = match flags & FLAG_RULE_VARIANTS {
+        0 => 0,
+        _ => reader.read(8)?,
+    };
+    if variants & !KNOWN_RULES != 0 {
+        return Err(SError::UnsupportedFlags(variants as u8));
+    }
+    let topology = match (variants & RULE_CYLINDER, variants & RULE_TORUS) {
+        (0, 0) => Topology::Flat,
+        (_, 0) => Topology::Cylinder,
+        (0, _) => Topology::Torus,
+        _ => return Err(SError::UnsupportedFlags(variants as u8)),
+    };
+    let promotion = if variants & RULE_PROMOTION_FIXED != 0 {
+        let code = reader.read(code_bits.max(4))? as u8;
+        match piece_from_code(code, &piece_definitions) {
+            Some(Some(PieceKind::King)) if flags & FLAG_ROYAL_KING != 0 => {
+                return Err(SError::InvalidPieceCode { position: 0, code });
+            }
+            Some(Some(kind)) => Some(Promotion::To(kind)),
+            _ => return Err(SError::InvalidPieceCode { position: 0, code }),
+        }
+    } else if variants & RULE_PROMOTION_CHOICE != 0 {
+        Some(Promotion::Choice)
+    } else {
+        None
+    };
+    let chained_square = match variants & RULE_CHAINED_SQUARE {
+        0 => None,
+        _ => Some(reader.read(6)? as usize),
+    };
+    let mut rules = Ruleset::with_age_limit(max_moves_per_piece);
+    if flags & FLAG_ROYAL_KING != 0 {
+        rules.add(RoyalKing);
+    }
+    if let Some(sq) = target_square {
+        rules.add(TargetSquare(sq as u8));
+    }
+    if let Some(promotion) = promotion {
+        rules.add(promotion);
+    }
+    if variants & RULE_CHAMELEON != 0 {
+        rules.add(Chameleon);
+    }
+    if variants & RULE_CHAIN_CAPTURE != 0 {
+        rules.add(ChainCapture);
+    }
+
+    // The board can only be created once every blocked square is known
+    let mut pieces = vec![];
+    let mut blocked = 0;
+    for file in 0..width {
+        for rank in 0..height {
+            let code = reader.read(code_bits)? as u8;
+            if code == BLOCKED_CODE {
+                blocked |= 1 << (rank * width + file);
+                continue;
+            }
+
+            let Some(kind) = decode_piece(code, file * height + rank, &piece_definitions)? else {
+                continue;
+            };
+
+            let moves_made = reader.read(age_bits)?;
+            let piece = Piece {
+                kind,
+                moves_made,
+                active: rules.can_move(moves_made),
+                target: target_piece == Some(rank * width + file),
+            };
+            pieces.push(Square::new(file, rank, height, Some(piece)));
+        }
+    }
+
+    let mut board = Board::create(BoardOptions {
+        rules,
+        width,
+        height,
+        blocked,
+        topology,
+        piece_definitions,
+    });
+    for square in pieces {
+        board.set(square);
+    }
+
+    if let Some(sq) = chained_square {
+        if sq >= width * height || !board.rules().chain_capture() {
+            return Err(SError::InvalidSquare(sq.to_string()));
+        }
+        board.continue_chain(sq % width, sq / width)?;
+    }
+
+    Ok(board)
+}
+
+fn read_piece_definitions(reader: &mut BitReader) -> Result<Vec<PieceDefinition>, SError> {
+    let count = reader.read(2)? as usize + 1;
+    let mut definitions: Vec<PieceDefinition> = vec![];
+    for _ in 0..count {
+        let letter = (b'A' + reader.read(5)? as u8) as char;
+        let len = reader.read(5)? as usize;
+        let mut betza = String::new();
+        for _ in 0..len {
+            betza.push(lookup(reader.read(6)? as u8));
+        }
+
+        let definition = PieceDefinition::new(letter, &betza)?;
+        if definitions.iter().any(|other| other.letter == letter) {
+            return Err(SError::InvalidPiece(letter.to_string()));
+        }
+        definitions.push(definition);
+    }
+
+    Ok(definitions)
+}
+
+/// Number of bits needed to store every age from 0 to `max_moves_per_piece`
+fn age_bits(max_moves_per_piece: u32) -> u32 {
+    u32::BITS - max_moves_per_piece.leading_zeros()
+}
+
+fn decode_piece(
+    code: u8,
+    position: usize,
+    definitions: &[PieceDefinition],
+) -> Result<Option<PieceKind>, SError> {
+    piece_from_code(code, definitions).ok_or(SError::InvalidPieceCode { position, code })
+}
+
+fn piece_code(kind: Option<PieceKind>, definitions: &[PieceDefinition]) -> u8 {
+    match kind {
+        Some(PieceKind::King) => 0b001,
+        Some(PieceKind::Queen) => 0b010,
+        Some(PieceKind::Rook) => 0b011,
+        Some(PieceKind::Bishop) => 0b100,
+        Some(PieceKind::Knight) => 0b101,
+        Some(PieceKind::Pawn) => 0b110,
+        Some(PieceKind::Archbishop) => 0b1000,
+        Some(PieceKind::Chancellor) => 0b1001,
+        Some(PieceKind::Amazon) => 0b1010,
+        Some(PieceKind::Camel) => 0b1011,
+        Some(PieceKind::Zebra) => 0b1100,
+        Some(PieceKind::Nightrider) => 0b1101,
+        Some(PieceKind::Custom(letter)) => {
+            let slot = definitions
+                .iter()
+                .position(|definition| definition.letter == letter)
+                .expect("Pieces on the board are defined for it");
+            FIRST_DEFINED_CODE + slot as u8
+        }
+        None => 0b000,
+    }
+}
+
+/// `None` for codes that are not assigned to any piece. Fairy pieces have 4
+/// bit codes and defined pieces 5 bit codes, so shorter codes never decode to
+/// them.
+fn piece_from_code(code: u8, definitions: &[PieceDefinition]) -> Option<Option<PieceKind>> {
+    match code {
+        0b001 => Some(Some(PieceKind::King)),
+        0b010 => Some(Some(PieceKind::Queen)),
+        0b011 => Some(Some(PieceKind::Rook)),
+        0b100 => Some(Some(PieceKind::Bishop)),
+        0b101 => Some(Some(PieceKind::Knight)),
+        0b110 => Some(Some(PieceKind::Pawn)),
+        0b1000 => Some(Some(PieceKind::Archbishop)),
+        0b1001 => Some(Some(PieceKind::Chancellor)),
+        0b1010 => Some(Some(PieceKind::Amazon)),
+        0b1011 => Some(Some(PieceKind::Camel)),
+        0b1100 => Some(Some(PieceKind::Zebra)),
+        0b1101 => Some(Some(PieceKind::Nightrider)),
+        0b000 => Some(None),
+        code if code >= FIRST_DEFINED_CODE => definitions
+            .get((code - FIRST_DEFINED_CODE) as usize)
+            .map(|definition| Some(definition.kind())),
+        _ => None,
+    }
+}
+
+/// Packs values most significant bit first into 6 bit characters
+#[derive(Default)]
+struct BitWriter {
+    sextets: Vec<u8>,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, width: u32) {
+        for bit in (0..width).rev() {
+            if self.bits.is_multiple_of(6) {
+                self.sextets.push(0);
+            }
+
+            let last = self.sextets.last_mut().unwrap();
+            *last |= (((value >> bit) & 1) as u8) << (5 - self.bits % 6);
+            self.bits += 1;
+        }
+    }
+
+    fn finish(self) -> String {
+        self.sextets.into_iter().map(lookup).collect()
+    }
+}
+
+struct BitReader<'a> {
+    sextets: &'a [u8],
+    bits: usize,
+
+    // Length of the whole id, reported when the id is too short
+    id_len: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(sextets: &'a [u8], id_len: usize) -> Self {
+        BitReader {
+            sextets,
+            bits: 0,
+            id_len,
+        }
+    }
+
+    fn read(&mut self, width: u32) -> Result<u32, SError> {
+        let mut value = 0;
+        for _ in 0..width {
+            let sextet = self
+                .sextets
+                .get(self.bits / 6)
+                .ok_or(SError::InvalidLength(self.id_len))?;
+            value = value << 1 | ((sextet >> (5 - self.bits % 6)) & 1) as u32;
+            self.bits += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+fn b64_decode(input: &str) -> Result<Vec<u8>, SError> {
+    input
+        .chars()
+        .enumerate()
+        .map(|(position, found)| {
+            reverse_lookup(found).ok_or(SError::InvalidChar { position, found })
+        })
+        .collect()
+}
+
+fn lookup(idx: u8) -> char {
+    ALPHABET.chars().nth(idx as usize).unwrap()
+}
+
+fn reverse_lookup(c: char) -> Option<u8> {
+    ALPHABET.chars().position(|x| x == c).map(|idx| idx as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::cmove::CMove;
+
+    fn sq(notation: &str, width: usize, height: usize) -> Square {
+        Square::parse(notation, width, height).unwrap()
+    }
+
+    #[test]
+    fn test_bit_writer_reader() {
+        let mut writer = BitWriter::default();
+        writer.write(0b101, 3);
+        writer.write(0b1, 1);
+        writer.write(0xAB, 8);
+        let id = writer.finish();
+        assert_eq!(2, id.len());
+
+        let sextets = b64_decode(&id).unwrap();
+        let mut reader = BitReader::new(&sextets, id.len());
+        assert_eq!(Ok(0b101), reader.read(3));
+        assert_eq!(Ok(0b1), reader.read(1));
+        assert_eq!(Ok(0xAB), reader.read(8));
+        assert_eq!(Err(SError::InvalidLength(2)), reader.read(1));
+    }
+
+    #[test]
+    fn test_v1_ids() {
+        // Ids handed out before v2 keep decoding to the same puzzle
+        let board = decode("wmgYAAAG").unwrap();
+        assert_eq!("PB..\n....\nBR..\nP..P\n", board.print(false));
+        assert_eq!(2, board.max_moves_per_piece());
+        assert_eq!("wmgYAAAG", board.id);
+    }
+
+    #[test]
+    fn test_v2_round_trips_ages() {
+        let mut board = Board::create(BoardOptions {
+            rules: Ruleset::with_age_limit(5),
+            ..Default::default()
+        });
+        board.set(sq("Ra1", 4, 4));
+        board.set(sq("Rd1", 4, 4));
+        board.set(sq("Nb3", 4, 4));
+        assert!(board.id.starts_with('C'));
+
+        let mv = board.legal_moves.iter().next().unwrap().clone();
+        board.make_move(mv);
+        let decoded = decode(&board.id).unwrap();
+        assert_eq!(5, decoded.max_moves_per_piece());
+        assert_eq!(board.print(false), decoded.print(false));
+        assert_eq!(board.cells, decoded.cells);
+        assert!(board.legal_moves == decoded.legal_moves);
+        assert_eq!(board.id, decoded.id);
+    }
+
+    #[test]
+    fn test_v2_dimensions() {
+        for (width, height) in [(1, 1), (2, 3), (3, 2), (5, 7), (8, 8)] {
+            let mut board = Board::create(BoardOptions {
+                width,
+                height,
+                ..Default::default()
+            });
+            board.set(sq(
+                &format!("Q{}1", (b'a' + width as u8 - 1) as char),
+                width,
+                height,
+            ));
+            assert_ne!(V1_LEN, board.id.len());
+
+            let decoded = decode(&board.id).unwrap();
+            assert_eq!(width, decoded.width);
+            assert_eq!(height, decoded.height);
+            assert_eq!(board.print(false), decoded.print(false));
+        }
+
+        // An empty 2x3 board fills exactly 8 characters before padding
+        let board = Board::create(BoardOptions {
+            width: 2,
+            height: 3,
+            ..Default::default()
+        });
+        assert_eq!(V1_LEN + 1, board.id.len());
+        assert_eq!(3, decode(&board.id).unwrap().height);
+    }
+
+    #[test]
+    fn test_v2_errors() {
+        assert_eq!(Err(SError::InvalidLength(5)), decode("DAAAA").map(|_| ()));
+        assert_eq!(Err(SError::InvalidLength(3)), decode("CAA").map(|_| ()));
+
+        // 4x4, age limit 2, an unknown rule variant set
+        assert_eq!(
+            Err(SError::UnsupportedFlags(128)),
+            decode("CbAkIAAAAAAAAA").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_v2_fairy_pieces() {
+        let mut board = Board::new();
+        board.set(sq("Ab2", 4, 4));
+        board.set(sq("Hc3", 4, 4));
+        board.set(sq("Rd1", 4, 4));
+        assert!(board.id.starts_with('C'));
+
+        let decoded = decode(&board.id).unwrap();
+        assert_eq!(board.cells, decoded.cells);
+        assert_eq!(board.id, decoded.id);
+
+        // Without fairy pieces the same board keeps its 3 bit codes
+        board.set(Square::new(1, 2, 4, None));
+        board.set(Square::new(2, 1, 4, None));
+        assert_eq!(V1_LEN, board.id.len());
+    }
+
+    #[test]
+    fn test_v2_piece_definitions() {
+        for fen in [
+            "X3/4/1A2/3R 2 piece=X:WD",
+            "X3/1P2/Y3/3R 3 piece=X:fWbF2 piece=Y:NN promote=Y",
+            "4/4/4/R3 2 piece=D:D piece=E:vW piece=F:sW piece=G:G",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(board.id.starts_with('C'));
+
+            let decoded = decode(&board.id).unwrap();
+            assert_eq!(board.piece_definitions(), decoded.piece_definitions());
+            assert_eq!(board.rules(), decoded.rules());
+            assert_eq!(board.cells, decoded.cells);
+            assert_eq!(board.to_fen(), decoded.to_fen());
+        }
+    }
+
+    #[test]
+    fn test_v2_rules() {
+        let mut board = Board::create(BoardOptions {
+            rules: Ruleset::solo_chess(2),
+            ..Default::default()
+        });
+        board.set(sq("Kc1", 4, 4));
+        board.set(sq("Rd1", 4, 4));
+        assert!(board.id.starts_with('C'));
+
+        let decoded = decode(&board.id).unwrap();
+        assert_eq!(board.rules(), decoded.rules());
+        assert_eq!(board.cells, decoded.cells);
+        assert_eq!(1, decoded.legal_moves.len());
+    }
+
+    #[test]
+    fn test_v2_targets() {
+        let board = Board::from_fen("4/1N*2/4/R2R 2 target=c3").unwrap();
+        assert!(board.id.starts_with('C'));
+
+        let decoded = decode(&board.id).unwrap();
+        assert_eq!(board.rules(), decoded.rules());
+        assert_eq!(board.cells, decoded.cells);
+        assert_eq!(board.to_fen(), decoded.to_fen());
+
+        // Only a target piece still needs a v2 id
+        let board = Board::from_fen("4/1N*2/4/R2R").unwrap();
+        assert_ne!(V1_LEN, board.id.len());
+        assert_eq!(board.cells, decode(&board.id).unwrap().cells);
+    }
+
+    #[test]
+    fn test_v2_blocked_squares() {
+        let board = Board::from_fen("#R1#/4/2#1/N2# 2").unwrap();
+        assert!(board.id.starts_with('C'));
+
+        let decoded = decode(&board.id).unwrap();
+        assert_eq!(board.blocked(), decoded.blocked());
+        assert_eq!(board.cells, decoded.cells);
+        assert_eq!(board.to_fen(), decoded.to_fen());
+
+        // The blocked code is not a piece in v1 ids
+        assert_eq!(
+            Err(SError::InvalidPieceCode {
+                position: 0,
+                code: BLOCKED_CODE
+            }),
+            decode("4AAAAAAA").map(|_| ())
+        );
+
+        // Next to fairy pieces the blocked code is widened to 4 bits
+        let board = Board::from_fen("#A2/4/4/4 2").unwrap();
+        let decoded = decode(&board.id).unwrap();
+        assert!(decoded.is_blocked(0, 0));
+        assert_eq!(board.cells, decoded.cells);
+    }
+
+    #[test]
+    fn test_v2_rule_variants() {
+        for fen in [
+            "N3/1P2/4/3R 2 promote",
+            "N3/1P2/4/3R 2 promote=Q",
+            "N3/1P2/4/3R 2 promote=H",
+            "N3/1P2/4/3R 2 chameleon",
+            "N3/1P2/4/3R 2 promote=B chameleon",
+            "N3/1P2/4/3R 2 chain",
+            "N3/1P2/4/3R 2 cylinder",
+            "N3/1P2/4/3R 2 promote torus",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(board.id.starts_with('C'));
+
+            let decoded = decode(&board.id).unwrap();
+            assert_eq!(board.rules(), decoded.rules());
+            assert_eq!(board.topology(), decoded.topology());
+            assert_eq!(board.cells, decoded.cells);
+        }
+    }
+
+    #[test]
+    fn test_v2_chained_square() {
+        let mut board = Board::from_fen("R2N/4/4/B2P 3 chain").unwrap();
+        board.make_move(CMove::parse(&board, "RxNd4").unwrap());
+
+        let decoded = decode(&board.id).unwrap();
+        assert_eq!(board.to_fen(), decoded.to_fen());
+        assert_eq!(board.id, decoded.id);
+        assert!(board.legal_moves == decoded.legal_moves);
+        assert_eq!(1, decoded.legal_moves.len());
+    }
+}
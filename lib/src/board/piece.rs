@@ -1,8 +1,13 @@
+use super::{betza, constants::MAX_BETZA_LEN, errors::SError};
+
 #[derive(Clone, Eq, Hash, Copy, Debug, PartialEq)]
 pub struct Piece {
     pub kind: PieceKind,
     pub moves_made: u32,
     pub active: bool,
+
+    // The piece that has to be the last one standing, it can not be captured
+    pub target: bool,
 }
 impl Piece {
     pub fn new(kind: PieceKind) -> Self {
@@ -10,6 +15,7 @@ impl Piece {
             kind,
             moves_made: 0,
             active: true,
+            target: false,
         }
     }
 
@@ -18,7 +24,7 @@ impl Piece {
     }
 }
 
-#[derive(Clone, Eq, Hash, Copy, Debug, PartialEq)]
+#[derive(Clone, Eq, Hash, Copy, Debug, PartialEq, PartialOrd, Ord)]
 pub enum PieceKind {
     King,
     Queen,
@@ -26,19 +32,77 @@ pub enum PieceKind {
     Knight,
     Rook,
     Pawn,
+
+    // Fairy pieces
+    Archbishop,
+    Chancellor,
+    Amazon,
+    Camel,
+    Zebra,
+    Nightrider,
+
+    // A piece defined at runtime by a `PieceDefinition`, named by its letter
+    Custom(char),
+}
+
+/// A piece defined at runtime by its letter and how it captures in Betza
+/// notation, like `X:WD` for a piece that leaps one or two squares
+/// orthogonally. Boards are created with the definitions of the pieces they
+/// use on top of the built in ones.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PieceDefinition {
+    pub letter: char,
+    pub betza: String,
 }
 
 impl PieceKind {
-    pub fn parse(piece: &str) -> Option<Self> {
+    /// Every built in kind, in the order of their bitboards in `Position`
+    pub const ALL: [PieceKind; 12] = [
+        PieceKind::King,
+        PieceKind::Queen,
+        PieceKind::Bishop,
+        PieceKind::Knight,
+        PieceKind::Rook,
+        PieceKind::Pawn,
+        PieceKind::Archbishop,
+        PieceKind::Chancellor,
+        PieceKind::Amazon,
+        PieceKind::Camel,
+        PieceKind::Zebra,
+        PieceKind::Nightrider,
+    ];
+
+    /// Parses the letter of a built in piece or of one of the `definitions`
+    pub fn parse_with(
+        piece: &str,
+        definitions: &[PieceDefinition],
+    ) -> Result<Option<Self>, SError> {
+        let defined = definitions
+            .iter()
+            .find(|definition| piece.chars().eq([definition.letter]));
+        match defined {
+            Some(definition) => Ok(Some(definition.kind())),
+            None => PieceKind::parse(piece),
+        }
+    }
+
+    /// Parses a piece letter, `.` being an empty square
+    pub fn parse(piece: &str) -> Result<Option<Self>, SError> {
         match piece {
-            "K" => Some(PieceKind::King),
-            "Q" => Some(PieceKind::Queen),
-            "B" => Some(PieceKind::Bishop),
-            "N" => Some(PieceKind::Knight),
-            "R" => Some(PieceKind::Rook),
-            "P" => Some(PieceKind::Pawn),
-            "." => None,
-            p => panic!("Invalid piece {}", p),
+            "K" => Ok(Some(PieceKind::King)),
+            "Q" => Ok(Some(PieceKind::Queen)),
+            "B" => Ok(Some(PieceKind::Bishop)),
+            "N" => Ok(Some(PieceKind::Knight)),
+            "R" => Ok(Some(PieceKind::Rook)),
+            "P" => Ok(Some(PieceKind::Pawn)),
+            "A" => Ok(Some(PieceKind::Archbishop)),
+            "C" => Ok(Some(PieceKind::Chancellor)),
+            "M" => Ok(Some(PieceKind::Amazon)),
+            "L" => Ok(Some(PieceKind::Camel)),
+            "Z" => Ok(Some(PieceKind::Zebra)),
+            "H" => Ok(Some(PieceKind::Nightrider)),
+            "." => Ok(None),
+            p => Err(SError::InvalidPiece(p.to_string())),
         }
     }
 
@@ -50,11 +114,53 @@ impl PieceKind {
             PieceKind::Knight => "N",
             PieceKind::Rook => "R",
             PieceKind::Pawn => "P",
+            PieceKind::Archbishop => "A",
+            PieceKind::Chancellor => "C",
+            PieceKind::Amazon => "M",
+            PieceKind::Camel => "L",
+            PieceKind::Zebra => "Z",
+            PieceKind::Nightrider => "H",
+            PieceKind::Custom(letter) => return letter.to_string(),
         };
 
         n.to_string()
     }
 
+    /// How a built in piece captures, in Betza notation, see `betza` for what
+    /// is supported. Every move is a capture, so the pawn only has its
+    /// captures. Custom pieces capture as their `PieceDefinition` says.
+    pub fn betza(&self) -> Option<&'static str> {
+        let betza = match self {
+            PieceKind::King => "K",
+            PieceKind::Queen => "Q",
+            PieceKind::Bishop => "B",
+            PieceKind::Knight => "N",
+            PieceKind::Rook => "R",
+            PieceKind::Pawn => "fF",
+            PieceKind::Archbishop => "BN",
+            PieceKind::Chancellor => "RN",
+            PieceKind::Amazon => "QN",
+            PieceKind::Camel => "C",
+            PieceKind::Zebra => "Z",
+            PieceKind::Nightrider => "NN",
+            PieceKind::Custom(_) => return None,
+        };
+
+        Some(betza)
+    }
+
+    pub fn is_fairy(&self) -> bool {
+        !matches!(
+            self,
+            PieceKind::King
+                | PieceKind::Queen
+                | PieceKind::Bishop
+                | PieceKind::Knight
+                | PieceKind::Rook
+                | PieceKind::Pawn
+        )
+    }
+
     pub fn pretty(&self) -> String {
         let n = match self {
             PieceKind::King => "♔",
@@ -63,19 +169,64 @@ impl PieceKind {
             PieceKind::Knight => "♘",
             PieceKind::Rook => "♖",
             PieceKind::Pawn => "♙",
+
+            // No symbols for these, the letter has to do
+            fairy => return fairy.notation(),
         };
 
         n.to_string()
     }
 }
 
+impl PieceDefinition {
+    /// Defines the piece `letter`, which has to be an upper case letter that
+    /// no built in piece uses, capturing as `betza` says
+    pub fn new(letter: char, betza: &str) -> Result<Self, SError> {
+        let built_in = PieceKind::parse(&letter.to_string()).is_ok();
+        if !letter.is_ascii_uppercase() || built_in {
+            return Err(SError::InvalidPiece(letter.to_string()));
+        }
+
+        // Ids hold the length of the definition in 5 bits
+        if betza.len() > MAX_BETZA_LEN {
+            return Err(SError::InvalidBetza(format!("Too long: {}", betza)));
+        }
+        betza::parse(betza)?;
+
+        Ok(PieceDefinition {
+            letter,
+            betza: betza.to_string(),
+        })
+    }
+
+    /// Parses a definition written as the letter and the Betza notation,
+    /// separated by `:`, like `X:WD`
+    pub fn parse(definition: &str) -> Result<Self, SError> {
+        let invalid = || SError::InvalidPiece(definition.to_string());
+        let (letter, betza) = definition.split_once(':').ok_or_else(invalid)?;
+        let mut letters = letter.chars();
+        match (letters.next(), letters.next()) {
+            (Some(letter), None) => PieceDefinition::new(letter, betza),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn kind(&self) -> PieceKind {
+        PieceKind::Custom(self.letter)
+    }
+
+    pub fn notation(&self) -> String {
+        format!("{}:{}", self.letter, self.betza)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     macro_rules! p {
         ($piece:literal) => {
-            PieceKind::parse($piece)
+            PieceKind::parse($piece).unwrap()
         };
     }
 
@@ -87,5 +238,58 @@ mod tests {
         assert_eq!(p!("N"), Some(PieceKind::Knight));
         assert_eq!(p!("R"), Some(PieceKind::Rook));
         assert_eq!(p!("P"), Some(PieceKind::Pawn));
+        assert_eq!(p!("A"), Some(PieceKind::Archbishop));
+        assert_eq!(p!("H"), Some(PieceKind::Nightrider));
+    }
+
+    #[test]
+    fn test_piece_notation_round_trip() {
+        for kind in PieceKind::ALL {
+            assert_eq!(Ok(Some(kind)), PieceKind::parse(&kind.notation()));
+            assert!(kind.betza().is_some());
+        }
+        assert_eq!("♘", PieceKind::Knight.pretty());
+        assert_eq!("L", PieceKind::Camel.pretty());
+    }
+
+    #[test]
+    fn test_piece_parse_invalid() {
+        assert_eq!(Ok(None), PieceKind::parse("."));
+        assert_eq!(
+            Err(SError::InvalidPiece("X".to_string())),
+            PieceKind::parse("X")
+        );
+        assert!(PieceKind::parse("").is_err());
+        assert!(PieceKind::parse("KQ").is_err());
+    }
+
+    #[test]
+    fn test_piece_definition() {
+        let definition = PieceDefinition::parse("X:WD").unwrap();
+        assert_eq!(PieceKind::Custom('X'), definition.kind());
+        assert_eq!("X:WD", definition.notation());
+        assert_eq!("X", definition.kind().pretty());
+        assert_eq!(None, definition.kind().betza());
+
+        let definitions = [definition];
+        assert_eq!(
+            Ok(Some(PieceKind::Custom('X'))),
+            PieceKind::parse_with("X", &definitions)
+        );
+        assert_eq!(
+            Ok(Some(PieceKind::Knight)),
+            PieceKind::parse_with("N", &definitions)
+        );
+        assert!(PieceKind::parse_with("Y", &definitions).is_err());
+
+        // Letters of built in pieces, lower case letters and bad Betza
+        for definition in ["N:WD", "x:WD", "XY:WD", "X", "X:", "X:J", "X:mW"] {
+            assert!(
+                PieceDefinition::parse(definition).is_err(),
+                "expected invalid definition: {}",
+                definition
+            );
+        }
+        assert!(PieceDefinition::new('X', &"W".repeat(32)).is_err());
     }
 }
@@ -0,0 +1,805 @@
+use std::sync::Arc;
+
+use super::{
+    Board, Topology,
+    betza::{self, MoveRule},
+    cmove::CMove,
+    constants::{MAX_BOARD_SIZE, MAX_PIECE_DEFINITIONS},
+    piece::{Piece, PieceDefinition, PieceKind},
+    ruleset::Ruleset,
+    square::Square,
+};
+
+pub const MAX_SQUARES: usize = MAX_BOARD_SIZE * MAX_BOARD_SIZE;
+
+// Built in kinds followed by the pieces defined at runtime
+const MAX_KINDS: usize = PieceKind::ALL.len() + MAX_PIECE_DEFINITIONS;
+
+// (file, rank) steps. Ranks are counted from the top of the board, so a
+// negative rank step moves up the board.
+const DIRECTIONS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+/// Squares attacked from every square of a board, computed once per board
+/// shape from the Betza definition of every piece kind, built in or defined
+/// for the board. Squares are indexed as `rank * width + file`.
+#[derive(Default, Debug)]
+pub struct AttackTables {
+    pub width: usize,
+    pub height: usize,
+
+    // Squares that are not part of the board. Nothing can stand on them or
+    // ride through them.
+    pub blocked: u64,
+
+    pub topology: Topology,
+
+    pub definitions: Vec<PieceDefinition>,
+
+    // Per piece kind, in the order of `CustomKinds::kinds`
+    custom: CustomKinds,
+    kinds: Vec<KindAttacks>,
+
+    // All squares from a square towards the board edge, per direction. Only
+    // used on flat boards, where the nearest square of a ray is the one with
+    // the lowest or highest index.
+    rays: [Vec<u64>; 8],
+}
+
+#[derive(Default, Debug)]
+struct KindAttacks {
+    // Squares reached by a leap, per square
+    leaps: Vec<u64>,
+
+    // Unlimited rides along `DIRECTIONS`, which use the precomputed `rays`
+    slides: Vec<usize>,
+
+    // The squares of every other ride in the order they are stepped on, per
+    // square
+    rides: Vec<Vec<Vec<u8>>>,
+}
+
+/// The letters of the pieces defined for a board, whose bitboards and
+/// attacks follow those of the built in kinds in this order
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+struct CustomKinds([Option<char>; MAX_PIECE_DEFINITIONS]);
+
+/// A board reduced to bitmasks: one occupancy mask per piece kind plus the
+/// number of moves made by the piece on each square.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Position {
+    pieces: [u64; MAX_KINDS],
+    ages: [u8; MAX_SQUARES],
+    custom: CustomKinds,
+
+    // The square of the target piece, if there is one
+    target: u64,
+
+    // With chain captures, the square of the only piece that may move next.
+    // No squares before the first capture.
+    chain: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PositionMove {
+    pub from: u8,
+    pub to: u8,
+
+    // What a pawn capturing onto the top rank becomes
+    pub promotion: Option<PieceKind>,
+}
+
+impl CustomKinds {
+    fn new(definitions: &[PieceDefinition]) -> Self {
+        let mut custom = CustomKinds::default();
+        for (letter, definition) in custom.0.iter_mut().zip(definitions) {
+            *letter = Some(definition.letter);
+        }
+
+        custom
+    }
+
+    /// Index of the bitboard and the attacks of `kind`, if it is built in
+    /// or defined
+    fn index(&self, kind: PieceKind) -> Option<usize> {
+        let index = match kind {
+            PieceKind::King => 0,
+            PieceKind::Queen => 1,
+            PieceKind::Bishop => 2,
+            PieceKind::Knight => 3,
+            PieceKind::Rook => 4,
+            PieceKind::Pawn => 5,
+            PieceKind::Archbishop => 6,
+            PieceKind::Chancellor => 7,
+            PieceKind::Amazon => 8,
+            PieceKind::Camel => 9,
+            PieceKind::Zebra => 10,
+            PieceKind::Nightrider => 11,
+            PieceKind::Custom(letter) => {
+                let slot = self.0.iter().position(|c| *c == Some(letter))?;
+                PieceKind::ALL.len() + slot
+            }
+        };
+
+        Some(index)
+    }
+
+    /// Every kind, built in kinds first
+    fn kinds(&self) -> impl Iterator<Item = PieceKind> + '_ {
+        let custom = self
+            .0
+            .iter()
+            .map_while(|letter| letter.map(PieceKind::Custom));
+        PieceKind::ALL.into_iter().chain(custom)
+    }
+}
+
+impl AttackTables {
+    /// Panics if more than `MAX_PIECE_DEFINITIONS` pieces are defined, or a
+    /// letter is defined twice
+    pub fn new(
+        width: usize,
+        height: usize,
+        blocked: u64,
+        topology: Topology,
+        definitions: &[PieceDefinition],
+    ) -> Arc<Self> {
+        if definitions.len() > MAX_PIECE_DEFINITIONS {
+            panic!(
+                "At most {} pieces can be defined for a board",
+                MAX_PIECE_DEFINITIONS
+            );
+        }
+
+        let defined_twice = (1..definitions.len()).any(|i| {
+            definitions[..i]
+                .iter()
+                .any(|other| other.letter == definitions[i].letter)
+        });
+        if defined_twice {
+            panic!("Pieces can only be defined once for a board");
+        }
+
+        let num_squares = width * height;
+        let mut tables = AttackTables {
+            width,
+            height,
+            blocked: blocked & board_mask(num_squares),
+            topology,
+            definitions: definitions.to_vec(),
+            custom: CustomKinds::new(definitions),
+            kinds: vec![],
+            rays: Default::default(),
+        };
+
+        for (d, step) in DIRECTIONS.iter().enumerate() {
+            tables.rays[d] = (0..num_squares)
+                .map(|sq| {
+                    let (file, rank) = tables.coordinates(sq);
+                    tables
+                        .ride(file, rank, *step, None)
+                        .fold(0, |ray, to| ray | 1 << to)
+                })
+                .collect();
+        }
+
+        let betzas = PieceKind::ALL
+            .iter()
+            .map(|kind| kind.betza().expect("Built in pieces have their own"))
+            .chain(
+                definitions
+                    .iter()
+                    .map(|definition| definition.betza.as_str()),
+            );
+        tables.kinds = betzas
+            .map(|betza| {
+                let rules = betza::parse(betza).expect("Pieces are checked when defined");
+                tables.kind_attacks(&rules)
+            })
+            .collect();
+
+        Arc::new(tables)
+    }
+
+    fn kind_attacks(&self, rules: &[MoveRule]) -> KindAttacks {
+        let num_squares = self.width * self.height;
+        let mut attacks = KindAttacks {
+            leaps: vec![0; num_squares],
+            slides: vec![],
+            rides: vec![vec![]; num_squares],
+        };
+
+        for rule in rules {
+            match *rule {
+                MoveRule::Leap(step) => {
+                    for sq in 0..num_squares {
+                        let (file, rank) = self.coordinates(sq);
+                        // A leap can wrap all the way around to where it started
+                        if let Some((f, r)) = self.offset(file, rank, step)
+                            && (f, r) != (file, rank)
+                        {
+                            attacks.leaps[sq] |= 1 << self.index(f, r);
+                        }
+                    }
+                }
+                MoveRule::Ride { step, range: None }
+                    if self.topology == Topology::Flat && DIRECTIONS.contains(&step) =>
+                {
+                    let d = DIRECTIONS.iter().position(|s| *s == step).unwrap();
+                    attacks.slides.push(d);
+                }
+                MoveRule::Ride { step, range } => {
+                    for sq in 0..num_squares {
+                        let (file, rank) = self.coordinates(sq);
+                        let ride = self.ride(file, rank, step, range).collect::<Vec<_>>();
+                        if !ride.is_empty() {
+                            attacks.rides[sq].push(ride);
+                        }
+                    }
+                }
+            }
+        }
+
+        attacks
+    }
+
+    pub fn index(&self, file: usize, rank: usize) -> usize {
+        rank * self.width + file
+    }
+
+    /// Whether `kind` is built in or defined for the board
+    pub fn is_defined(&self, kind: PieceKind) -> bool {
+        self.custom.index(kind).is_some()
+    }
+
+    pub fn coordinates(&self, sq: usize) -> (usize, usize) {
+        (sq % self.width, sq / self.width)
+    }
+
+    /// Squares that a piece of `kind` on `sq` can capture, given the occupied
+    /// squares. Pieces that are not defined for the board capture nothing.
+    pub fn captures(&self, kind: PieceKind, sq: usize, occupied: u64) -> u64 {
+        let Some(index) = self.custom.index(kind) else {
+            return 0;
+        };
+        let kind = &self.kinds[index];
+        let mut attacks = kind.leaps[sq] | self.first_blockers(sq, occupied, &kind.slides);
+        for ride in &kind.rides[sq] {
+            if let Some(to) = ride.iter().find(|to| occupied & (1 << **to) != 0) {
+                attacks |= 1 << to;
+            }
+        }
+
+        attacks & occupied & !self.blocked
+    }
+
+    fn first_blockers(&self, sq: usize, occupied: u64, directions: &[usize]) -> u64 {
+        let mut blockers = 0;
+        for &d in directions {
+            let on_ray = self.rays[d][sq] & occupied;
+            if on_ray == 0 {
+                continue;
+            }
+
+            // Rays that step down the board or to the right walk towards
+            // higher square indices
+            let (dx, dy) = DIRECTIONS[d];
+            let nearest = if dy > 0 || (dy == 0 && dx > 0) {
+                on_ray.trailing_zeros()
+            } else {
+                63 - on_ray.leading_zeros()
+            };
+            blockers |= 1 << nearest;
+        }
+
+        blockers
+    }
+
+    /// The squares stepped on from (file, rank), nearest first, up to the
+    /// edge of the board, the first blocked square or, around a wrapping
+    /// board, the square the ride started from
+    fn ride(
+        &self,
+        file: usize,
+        rank: usize,
+        step: (i8, i8),
+        range: Option<u8>,
+    ) -> impl Iterator<Item = u8> {
+        let range = range.map_or(usize::MAX, |range| range as usize);
+        std::iter::successors(self.offset(file, rank, step), move |(f, r)| {
+            self.offset(*f, *r, step)
+        })
+        .take(range)
+        .take_while(move |square| *square != (file, rank))
+        .map(|(f, r)| self.index(f, r) as u8)
+        .take_while(|sq| self.blocked & (1 << sq) == 0)
+    }
+
+    fn offset(&self, file: usize, rank: usize, (dx, dy): (i8, i8)) -> Option<(usize, usize)> {
+        let file = wrap(file, dx, self.width, self.topology.wraps_files())?;
+        let rank = wrap(rank, dy, self.height, self.topology.wraps_ranks())?;
+        Some((file, rank))
+    }
+}
+
+impl Position {
+    pub fn from_board(board: &Board, tables: &AttackTables) -> Self {
+        let mut position = Position {
+            pieces: [0; MAX_KINDS],
+            ages: [0; MAX_SQUARES],
+            custom: tables.custom,
+            target: 0,
+            chain: 0,
+        };
+
+        for (file, ranks) in board.cells.iter().enumerate() {
+            for (rank, cell) in ranks.iter().enumerate() {
+                if let Some(piece) = cell {
+                    let sq = tables.index(file, rank);
+                    position.pieces[position.index(piece.kind)] |= 1 << sq;
+                    position.ages[sq] = piece.moves_made.min(u8::MAX as u32) as u8;
+                    if piece.target {
+                        position.target |= 1 << sq;
+                    }
+                }
+            }
+        }
+
+        if let Some(square) = board.chained_square() {
+            position.chain = 1 << tables.index(square.file, square.rank);
+        }
+
+        position
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.pieces.iter().fold(0, |occupied, mask| occupied | mask)
+    }
+
+    pub fn piece_count(&self) -> u32 {
+        self.occupied().count_ones()
+    }
+
+    /// Squares holding a piece of `kind`
+    pub fn pieces(&self, kind: PieceKind) -> u64 {
+        self.custom
+            .index(kind)
+            .map_or(0, |index| self.pieces[index])
+    }
+
+    /// Every kind that can be on the board, with the squares holding it
+    pub fn kinds(&self) -> impl Iterator<Item = (PieceKind, u64)> + '_ {
+        self.custom.kinds().zip(self.pieces.iter().copied())
+    }
+
+    pub fn kind_at(&self, sq: usize) -> Option<PieceKind> {
+        self.kinds()
+            .find(|(_, mask)| *mask & (1 << sq) != 0)
+            .map(|(kind, _)| kind)
+    }
+
+    pub fn age_at(&self, sq: usize) -> u8 {
+        self.ages[sq]
+    }
+
+    /// The square of the target piece, or no squares when there is none
+    pub fn target(&self) -> u64 {
+        self.target
+    }
+
+    /// Appends every capture that `rules` allow to `moves`
+    pub fn legal_moves(
+        &self,
+        tables: &AttackTables,
+        rules: &Ruleset,
+        moves: &mut Vec<PositionMove>,
+    ) {
+        let occupied = self.occupied();
+        let top_rank = (1 << tables.width) - 1;
+        for (kind, mask) in self.kinds() {
+            if mask == 0 {
+                continue;
+            }
+
+            // Every promotion is a move of its own
+            let promotions = match kind {
+                PieceKind::Pawn => rules.promotions(),
+                _ => &[],
+            };
+
+            let capturable = rules.capturable(kind, self);
+            let mut movers = match self.chain {
+                0 => mask,
+                chain => mask & chain,
+            };
+            while movers != 0 {
+                let from = movers.trailing_zeros() as usize;
+                movers &= movers - 1;
+                if !rules.can_move(self.ages[from] as u32) {
+                    continue;
+                }
+
+                let mut targets = tables.captures(kind, from, occupied) & capturable;
+                while targets != 0 {
+                    let to = targets.trailing_zeros();
+                    targets &= targets - 1;
+                    // A chameleon pawn only stays a pawn to promote when it
+                    // takes a pawn
+                    let promotes = !promotions.is_empty()
+                        && top_rank & (1 << to) != 0
+                        && self.kind_at(to as usize).is_some_and(|captured| {
+                            rules.kind_after_capture(PieceKind::Pawn, captured) == PieceKind::Pawn
+                        });
+                    if !promotes {
+                        moves.push(PositionMove {
+                            from: from as u8,
+                            to: to as u8,
+                            promotion: None,
+                        });
+                        continue;
+                    }
+
+                    for promotion in promotions {
+                        moves.push(PositionMove {
+                            from: from as u8,
+                            to: to as u8,
+                            promotion: Some(*promotion),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves the piece on `mv.from` onto `mv.to`, removing the captured piece.
+    /// The moving piece changes its kind if the move promotes it or if
+    /// `rules` make it a chameleon.
+    pub fn make_move(&mut self, mv: PositionMove, rules: &Ruleset) {
+        let from_bit = 1 << mv.from;
+        let to_bit = 1 << mv.to;
+        let (Some(kind), Some(captured)) =
+            (self.kind_at(mv.from as usize), self.kind_at(mv.to as usize))
+        else {
+            return;
+        };
+
+        let new_kind = mv
+            .promotion
+            .unwrap_or_else(|| rules.kind_after_capture(kind, captured));
+        self.pieces[self.index(captured)] &= !to_bit;
+        self.pieces[self.index(kind)] &= !from_bit;
+        self.pieces[self.index(new_kind)] |= to_bit;
+
+        if rules.chain_capture() {
+            self.chain = to_bit;
+        }
+
+        self.ages[mv.to as usize] = self.ages[mv.from as usize].saturating_add(1);
+        self.ages[mv.from as usize] = 0;
+        if self.target & from_bit != 0 {
+            self.target = to_bit;
+        }
+    }
+
+    /// Builds the `CMove` that `Board::legal_moves` would hold for `mv`
+    pub fn to_cmove(&self, mv: PositionMove, tables: &AttackTables, rules: &Ruleset) -> CMove {
+        let from = self.square(mv.from as usize, tables, rules);
+        let to = self.square(mv.to as usize, tables, rules);
+        let mut cmove = CMove::new(from, to);
+        cmove.promotion = mv.promotion;
+        cmove.disambig = self.disambiguation(mv, tables, rules);
+        cmove
+    }
+
+    /// The file, rank or both of `mv.from` when another piece of the same
+    /// kind can also capture on `mv.to`, like the `d` in `RdxBb4`.
+    fn disambiguation(&self, mv: PositionMove, tables: &AttackTables, rules: &Ruleset) -> String {
+        let Some(kind) = self.kind_at(mv.from as usize) else {
+            return String::new();
+        };
+
+        // Pawn moves are already qualified by the file they start from
+        if kind == PieceKind::Pawn {
+            return String::new();
+        }
+
+        let occupied = self.occupied();
+        let (file, rank) = tables.coordinates(mv.from as usize);
+        let mut others = self.pieces(kind) & !(1 << mv.from);
+        let (mut ambiguous, mut same_file, mut same_rank) = (false, false, false);
+        while others != 0 {
+            let sq = others.trailing_zeros() as usize;
+            others &= others - 1;
+            if !rules.can_move(self.ages[sq] as u32)
+                || tables.captures(kind, sq, occupied) & (1 << mv.to) == 0
+            {
+                continue;
+            }
+
+            let (other_file, other_rank) = tables.coordinates(sq);
+            ambiguous = true;
+            same_file |= other_file == file;
+            same_rank |= other_rank == rank;
+        }
+
+        let from = Square::new(file, rank, tables.height, None);
+        match (ambiguous, same_file, same_rank) {
+            (false, _, _) => String::new(),
+            (true, false, _) => from.file_notation(),
+            (true, true, false) => from.rank_notation(),
+            (true, true, true) => from.file_notation() + &from.rank_notation(),
+        }
+    }
+
+    fn index(&self, kind: PieceKind) -> usize {
+        self.custom
+            .index(kind)
+            .expect("Pieces on the board are defined for it")
+    }
+
+    fn square(&self, sq: usize, tables: &AttackTables, rules: &Ruleset) -> Square {
+        let (file, rank) = tables.coordinates(sq);
+        let piece = self.kind_at(sq).map(|kind| {
+            let moves_made = self.ages[sq] as u32;
+            Piece {
+                kind,
+                moves_made,
+                active: rules.can_move(moves_made),
+                target: self.target & (1 << sq) != 0,
+            }
+        });
+
+        Square::new(file, rank, tables.height, piece)
+    }
+}
+
+/// Steps `coord` by `delta` on a line of `size` squares, around to the other
+/// end if the line `wraps`
+fn wrap(coord: usize, delta: i8, size: usize, wraps: bool) -> Option<usize> {
+    match wraps {
+        true => Some((coord as isize + delta as isize).rem_euclid(size as isize) as usize),
+        false => coord
+            .checked_add_signed(delta as isize)
+            .filter(|c| *c < size),
+    }
+}
+
+fn board_mask(num_squares: usize) -> u64 {
+    match num_squares {
+        MAX_SQUARES => u64::MAX,
+        _ => (1 << num_squares) - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attack_tables() {
+        let tables = AttackTables::new(4, 4, 0, Topology::Flat, &[]);
+        let b2 = tables.index(1, 2);
+        let bit = |file, rank| 1u64 << tables.index(file, rank);
+
+        // Knight on b2 jumps to a4, c4, d3 and d1
+        assert_eq!(
+            bit(0, 0) | bit(2, 0) | bit(3, 1) | bit(3, 3),
+            tables.captures(PieceKind::Knight, b2, u64::MAX)
+        );
+
+        // Pawn on b2 only captures upwards
+        assert_eq!(
+            bit(0, 1) | bit(2, 1),
+            tables.captures(PieceKind::Pawn, b2, u64::MAX)
+        );
+
+        // Rook on b2 stops at the first piece in every direction
+        let occupied = bit(1, 0) | bit(1, 1) | bit(3, 2) | bit(0, 3);
+        assert_eq!(
+            bit(1, 1) | bit(3, 2),
+            tables.captures(PieceKind::Rook, b2, occupied)
+        );
+    }
+
+    #[test]
+    fn test_position_moves() {
+        let board = Board::from_string("pb......br..p..p".to_string()).unwrap();
+        let tables = AttackTables::new(4, 4, 0, Topology::Flat, &[]);
+        let mut position = Position::from_board(&board, &tables);
+        assert_eq!(6, position.piece_count());
+
+        let mut moves = vec![];
+        position.legal_moves(&tables, &Ruleset::default(), &mut moves);
+        assert_eq!(board.legal_moves.len(), moves.len());
+
+        // RxBb4 moves the rook from b2 onto b4
+        let mv = PositionMove {
+            from: tables.index(1, 2) as u8,
+            to: tables.index(1, 0) as u8,
+            promotion: None,
+        };
+        assert_eq!(
+            "RxBb4",
+            position
+                .to_cmove(mv, &tables, &Ruleset::default())
+                .notation()
+        );
+        position.make_move(mv, &Ruleset::default());
+        assert_eq!(5, position.piece_count());
+        assert_eq!(Some(PieceKind::Rook), position.kind_at(mv.to as usize));
+        assert_eq!(None, position.kind_at(mv.from as usize));
+        assert_eq!(1, position.age_at(mv.to as usize));
+        assert_eq!(0, position.age_at(mv.from as usize));
+    }
+
+    #[test]
+    fn test_fairy_attack_tables() {
+        let tables = AttackTables::new(8, 8, 0, Topology::Flat, &[]);
+        let bit = |file, rank| 1u64 << tables.index(file, rank);
+        let a1 = tables.index(0, 7);
+
+        // Camels and zebras leap over everything in between
+        assert_eq!(
+            bit(1, 4) | bit(3, 6),
+            tables.captures(PieceKind::Camel, a1, u64::MAX)
+        );
+        assert_eq!(
+            bit(2, 4) | bit(3, 5),
+            tables.captures(PieceKind::Zebra, a1, u64::MAX)
+        );
+
+        // The nightrider on a1 stops at the first piece on b3, c5 and d7
+        assert_eq!(
+            bit(1, 5) | bit(2, 6),
+            tables.captures(PieceKind::Nightrider, a1, u64::MAX)
+        );
+        assert_eq!(
+            bit(2, 3),
+            tables.captures(PieceKind::Nightrider, a1, bit(2, 3) | bit(3, 1))
+        );
+
+        // The archbishop captures like a bishop and like a knight
+        let d4 = tables.index(3, 4);
+        for occupied in [u64::MAX, bit(0, 7) | bit(6, 1) | bit(4, 2), 0] {
+            assert_eq!(
+                tables.captures(PieceKind::Bishop, d4, occupied)
+                    | tables.captures(PieceKind::Knight, d4, occupied),
+                tables.captures(PieceKind::Archbishop, d4, occupied)
+            );
+        }
+    }
+
+    #[test]
+    fn test_defined_attack_tables() {
+        let definitions = [
+            PieceDefinition::new('X', "WD").unwrap(),
+            PieceDefinition::new('Y', "fR2").unwrap(),
+        ];
+        let tables = AttackTables::new(4, 4, 0, Topology::Flat, &definitions);
+        let bit = |file, rank| 1u64 << tables.index(file, rank);
+        let b2 = tables.index(1, 2);
+
+        // X on b2 leaps to the squares one and two steps away
+        assert_eq!(
+            bit(0, 2) | bit(2, 2) | bit(3, 2) | bit(1, 1) | bit(1, 0) | bit(1, 3),
+            tables.captures(PieceKind::Custom('X'), b2, u64::MAX)
+        );
+
+        // Y on b2 rides up the file for at most two squares
+        assert_eq!(
+            bit(1, 0),
+            tables.captures(PieceKind::Custom('Y'), b2, bit(1, 0) | bit(0, 0))
+        );
+        assert!(!tables.is_defined(PieceKind::Custom('Z')));
+        assert_eq!(0, tables.captures(PieceKind::Custom('Z'), b2, u64::MAX));
+
+        let board = Board::from_fen("1Y2/4/1X2/4 2 piece=X:WD piece=Y:fR2").unwrap();
+        let position = board.position();
+        assert_eq!(Some(PieceKind::Custom('X')), position.kind_at(b2));
+        assert_eq!(bit(1, 0), position.pieces(PieceKind::Custom('Y')));
+
+        // Y only rides up the board, so X is the only piece that captures
+        let notations = board
+            .legal_moves
+            .iter()
+            .map(|mv| mv.notation())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["XxYb4"], notations);
+    }
+
+    #[test]
+    fn test_fairy_legal_moves() {
+        // . . . .
+        // . C . .
+        // . . . .
+        // H . . .
+        // The nightrider and the chancellor both capture with a knight leap
+        let board = Board::from_fen("4/1C2/4/H3 2").unwrap();
+        let mut notations = board
+            .legal_moves
+            .iter()
+            .map(|mv| mv.notation())
+            .collect::<Vec<_>>();
+        notations.sort();
+        assert_eq!(vec!["CxHa1", "HxCb3"], notations);
+        assert_eq!(2, board.solution_count());
+    }
+
+    #[test]
+    fn test_blocked_squares() {
+        let open = AttackTables::new(4, 4, 0, Topology::Flat, &[]);
+        let bit = |file, rank| 1u64 << open.index(file, rank);
+        let blocked = AttackTables::new(4, 4, bit(1, 1), Topology::Flat, &[]);
+
+        // The queen on a4 can not ride through b3 to d1
+        let a4 = open.index(0, 0);
+        let occupied = bit(3, 0) | bit(3, 3);
+        assert_eq!(occupied, open.captures(PieceKind::Queen, a4, occupied));
+        assert_eq!(bit(3, 0), blocked.captures(PieceKind::Queen, a4, occupied));
+
+        // The knight on c1 can not land on b3
+        let c1 = open.index(2, 3);
+        assert_eq!(
+            bit(0, 2) | bit(1, 1) | bit(3, 1),
+            open.captures(PieceKind::Knight, c1, u64::MAX)
+        );
+        assert_eq!(
+            bit(0, 2) | bit(3, 1),
+            blocked.captures(PieceKind::Knight, c1, u64::MAX)
+        );
+
+        // Bits past the board are ignored
+        assert_eq!(
+            0,
+            AttackTables::new(2, 2, 1 << 10, Topology::Flat, &[]).blocked
+        );
+    }
+
+    #[test]
+    fn test_wrapping_topology() {
+        let cylinder = AttackTables::new(4, 4, 0, Topology::Cylinder, &[]);
+        let torus = AttackTables::new(4, 4, 0, Topology::Torus, &[]);
+        let bit = |file, rank| 1u64 << cylinder.index(file, rank);
+
+        // The rook on a2 reaches d2 across the edge, but never itself
+        let a2 = cylinder.index(0, 2);
+        let occupied = bit(0, 2) | bit(1, 2) | bit(3, 2);
+        assert_eq!(
+            bit(1, 2) | bit(3, 2),
+            cylinder.captures(PieceKind::Rook, a2, occupied)
+        );
+        assert_eq!(0, torus.captures(PieceKind::Rook, a2, bit(0, 2)));
+
+        // The pawn on a2 captures onto b3 and d3
+        assert_eq!(
+            bit(1, 1) | bit(3, 1),
+            cylinder.captures(PieceKind::Pawn, a2, u64::MAX)
+        );
+
+        // The knight on a1 also jumps to d3, and on the torus to c4
+        let a1 = cylinder.index(0, 3);
+        let flat = bit(1, 1) | bit(2, 2);
+        assert_eq!(
+            flat | bit(3, 1),
+            cylinder.captures(PieceKind::Knight, a1, u64::MAX)
+        );
+        assert_eq!(
+            flat | bit(3, 1) | bit(2, 0),
+            torus.captures(PieceKind::Knight, a1, u64::MAX)
+        );
+
+        // Blocked squares still stop a ride around the board
+        let blocked = AttackTables::new(4, 4, bit(3, 2), Topology::Cylinder, &[]);
+        assert_eq!(
+            bit(1, 2),
+            blocked.captures(PieceKind::Rook, a2, bit(1, 2) | bit(2, 2))
+        );
+    }
+}
@@ -0,0 +1,602 @@
+use std::{
+    any::Any,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use super::{BoardState, piece::PieceKind, position::Position};
+
+/// The rules a board is played by: which captures are legal, when the game is
+/// won or lost and how many moves a piece may make. `Board`, the solver and
+/// the generator all ask the ruleset rather than hard coding these. The rules
+/// of standard solitaire chess are the default, and every variant in the
+/// ruleset changes them as its `Variant` hooks say.
+#[derive(Clone, Debug)]
+pub struct Ruleset {
+    /// Number of moves a piece can make before it has to stay where it is
+    pub max_moves_per_piece: u32,
+
+    variants: Vec<Arc<dyn Variant>>,
+}
+
+/// A rule that changes how solitaire chess is played. Every hook defaults to
+/// the standard rules, so a variant only implements the ones it changes, and
+/// any number of variants can be combined in a `Ruleset`. FEN like strings
+/// and ids only carry the variants in this module.
+pub trait Variant: Any + Debug + Send + Sync {
+    /// Names the variant and its settings, like `promote=Q`. Rulesets with
+    /// variants of the same names play the same.
+    fn name(&self) -> String;
+
+    /// Whether a `piece` that can reach a `captured` piece may capture it
+    fn can_capture(&self, _piece: PieceKind, _captured: PieceKind) -> bool {
+        true
+    }
+
+    /// Whether a `piece` stays the kind it is whatever other variants say
+    fn keeps_kind(&self, _piece: PieceKind) -> bool {
+        false
+    }
+
+    /// The kind of a piece that was `kind` once it has captured a `captured`
+    /// piece, before any promotion
+    fn kind_after_capture(&self, kind: PieceKind, _captured: PieceKind) -> PieceKind {
+        kind
+    }
+
+    /// The pieces a pawn capturing onto the top rank can become, if the
+    /// variant promotes pawns
+    fn promotions(&self) -> Option<&[PieceKind]> {
+        None
+    }
+
+    /// Whether only the piece that captured last may move on
+    fn chains_captures(&self) -> bool {
+        false
+    }
+
+    /// Whether `position`, which has a single piece left, is won
+    fn is_won(&self, _position: &Position) -> bool {
+        true
+    }
+
+    /// Pieces that every board played by the variant has exactly one of
+    fn required_pieces(&self) -> Vec<PieceKind> {
+        vec![]
+    }
+}
+
+/// Solo Chess: the king can never be captured and has to be the last piece
+/// standing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoyalKing;
+
+/// The square that has to hold the last piece standing, as an index
+/// `rank * width + file` like the squares of a `Position`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargetSquare(pub u8);
+
+/// Pawns only capture upwards, so without promotion a pawn on the top rank
+/// can never move again. This is what it turns into instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Promotion {
+    /// Pawns always become this piece
+    To(PieceKind),
+
+    /// The player picks one of `PROMOTION_CHOICES`
+    Choice,
+}
+
+/// Chameleon captures: a capturing piece turns into the kind of piece it
+/// captured. The royal king of Solo Chess stays a king.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chameleon;
+
+/// Chain captures: after the first capture only the piece that made the last
+/// capture may move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainCapture;
+
+/// The pieces a pawn can be promoted to under `Promotion::Choice`
+pub const PROMOTION_CHOICES: [PieceKind; 4] = [
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+];
+
+impl Variant for RoyalKing {
+    fn name(&self) -> String {
+        "solo".to_string()
+    }
+
+    fn can_capture(&self, _piece: PieceKind, captured: PieceKind) -> bool {
+        captured != PieceKind::King
+    }
+
+    fn keeps_kind(&self, piece: PieceKind) -> bool {
+        piece == PieceKind::King
+    }
+
+    fn is_won(&self, position: &Position) -> bool {
+        position.pieces(PieceKind::King) != 0
+    }
+
+    fn required_pieces(&self) -> Vec<PieceKind> {
+        vec![PieceKind::King]
+    }
+}
+
+impl Variant for TargetSquare {
+    fn name(&self) -> String {
+        format!("target={}", self.0)
+    }
+
+    fn is_won(&self, position: &Position) -> bool {
+        position.occupied() & (1 << self.0) != 0
+    }
+}
+
+impl Variant for Promotion {
+    fn name(&self) -> String {
+        match self {
+            Promotion::To(kind) => format!("promote={}", kind.notation()),
+            Promotion::Choice => "promote".to_string(),
+        }
+    }
+
+    fn promotions(&self) -> Option<&[PieceKind]> {
+        match self {
+            Promotion::To(kind) => Some(std::slice::from_ref(kind)),
+            Promotion::Choice => Some(&PROMOTION_CHOICES),
+        }
+    }
+}
+
+impl Variant for Chameleon {
+    fn name(&self) -> String {
+        "chameleon".to_string()
+    }
+
+    fn kind_after_capture(&self, _kind: PieceKind, captured: PieceKind) -> PieceKind {
+        captured
+    }
+}
+
+impl Variant for ChainCapture {
+    fn name(&self) -> String {
+        "chain".to_string()
+    }
+
+    fn chains_captures(&self) -> bool {
+        true
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset::with_age_limit(2)
+    }
+}
+
+// Variants are compared by name, in any order
+impl PartialEq for Ruleset {
+    fn eq(&self, other: &Self) -> bool {
+        self.max_moves_per_piece == other.max_moves_per_piece && self.names() == other.names()
+    }
+}
+
+impl Eq for Ruleset {}
+
+impl Hash for Ruleset {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.max_moves_per_piece.hash(state);
+        self.names().hash(state);
+    }
+}
+
+impl Ruleset {
+    /// The default rules with a different age limit
+    pub fn with_age_limit(max_moves_per_piece: u32) -> Self {
+        Ruleset {
+            max_moves_per_piece,
+            variants: vec![],
+        }
+    }
+
+    /// The Solo Chess rules with the given age limit
+    pub fn solo_chess(max_moves_per_piece: u32) -> Self {
+        Ruleset::with_age_limit(max_moves_per_piece).with(RoyalKing)
+    }
+
+    /// These rules with `variant` added, see `add`
+    pub fn with(mut self, variant: impl Variant) -> Self {
+        self.add(variant);
+        self
+    }
+
+    /// Adds `variant` to the rules, in place of a variant of the same type.
+    /// Panics if that lets pawns promote to a second king in Solo Chess.
+    pub fn add<V: Variant>(&mut self, variant: V) {
+        self.remove::<V>();
+        self.variants.push(Arc::new(variant));
+        assert!(
+            !(self.royal_king() && self.promotion() == Some(Promotion::To(PieceKind::King))),
+            "Pawns can not promote to a king in Solo Chess"
+        );
+    }
+
+    /// Takes the variant of type `V` out of the rules, if it is in them
+    pub fn remove<V: Variant>(&mut self) {
+        self.variants
+            .retain(|variant| !(variant.as_ref() as &dyn Any).is::<V>());
+    }
+
+    /// The variant of type `V`, if it is in the rules
+    pub fn variant<V: Variant>(&self) -> Option<&V> {
+        self.variants
+            .iter()
+            .find_map(|variant| (variant.as_ref() as &dyn Any).downcast_ref::<V>())
+    }
+
+    /// Every variant in the rules, in the order they were added
+    pub fn variants(&self) -> &[Arc<dyn Variant>] {
+        &self.variants
+    }
+
+    /// Whether the rules are those of Solo Chess, see `RoyalKing`
+    pub fn royal_king(&self) -> bool {
+        self.variant::<RoyalKing>().is_some()
+    }
+
+    /// The square the last piece standing has to be on, see `TargetSquare`
+    pub fn target_square(&self) -> Option<u8> {
+        self.variant::<TargetSquare>().map(|target| target.0)
+    }
+
+    /// Whether capturing pieces turn into what they captured, see `Chameleon`
+    pub fn chameleon(&self) -> bool {
+        self.variant::<Chameleon>().is_some()
+    }
+
+    /// Whether only the piece that captured last may move on, see
+    /// `Variant::chains_captures`
+    pub fn chain_capture(&self) -> bool {
+        self.variants
+            .iter()
+            .any(|variant| variant.chains_captures())
+    }
+
+    /// What pawns promote to, if they do
+    pub fn promotion(&self) -> Option<Promotion> {
+        self.variant::<Promotion>().copied()
+    }
+
+    /// Whether a piece that has made `moves_made` moves can still move
+    pub fn can_move(&self, moves_made: u32) -> bool {
+        moves_made < self.max_moves_per_piece
+    }
+
+    /// Whether a `piece` that can reach a `captured` piece may capture it
+    pub fn can_capture(&self, piece: PieceKind, captured: PieceKind) -> bool {
+        self.variants
+            .iter()
+            .all(|variant| variant.can_capture(piece, captured))
+    }
+
+    /// The kind of a `piece` once it has captured a `captured` piece, before
+    /// any promotion
+    pub fn kind_after_capture(&self, piece: PieceKind, captured: PieceKind) -> PieceKind {
+        if self
+            .variants
+            .iter()
+            .any(|variant| variant.keeps_kind(piece))
+        {
+            return piece;
+        }
+
+        self.variants.iter().fold(piece, |kind, variant| {
+            variant.kind_after_capture(kind, captured)
+        })
+    }
+
+    /// The pieces a pawn capturing onto the top rank can become, none if
+    /// pawns do not promote
+    pub fn promotions(&self) -> &[PieceKind] {
+        self.variants
+            .iter()
+            .find_map(|variant| variant.promotions())
+            .unwrap_or(&[])
+    }
+
+    /// Squares of the pieces in `position` that a `piece` is allowed to
+    /// capture. The target piece can never be captured.
+    pub fn capturable(&self, piece: PieceKind, position: &Position) -> u64 {
+        let capturable = position
+            .kinds()
+            .filter(|(captured, mask)| *mask != 0 && self.can_capture(piece, *captured))
+            .fold(0, |capturable, (_, mask)| capturable | mask);
+
+        capturable & !position.target()
+    }
+
+    /// Whether the game is won once `position` is reached. As the target
+    /// piece is never captured, a single piece left is always the target.
+    pub fn is_won(&self, position: &Position) -> bool {
+        position.piece_count() == 1 && self.variants.iter().all(|variant| variant.is_won(position))
+    }
+
+    /// Pieces that every board played by these rules has exactly one of
+    pub fn required_pieces(&self) -> Vec<PieceKind> {
+        self.variants
+            .iter()
+            .flat_map(|variant| variant.required_pieces())
+            .collect()
+    }
+
+    pub fn game_state(&self, position: &Position, has_legal_moves: bool) -> BoardState {
+        if position.piece_count() == 0 {
+            BoardState::NotStarted
+        } else if self.is_won(position) {
+            BoardState::Won
+        } else if !has_legal_moves {
+            BoardState::Lost
+        } else {
+            BoardState::InProgress
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names = self
+            .variants
+            .iter()
+            .map(|variant| variant.name())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, BoardOptions, cmove::CMove, square::Square};
+
+    #[test]
+    fn test_default_rules() {
+        let rules = Ruleset::default();
+        assert!(rules.can_move(1));
+        assert!(!rules.can_move(2));
+        assert!(rules.can_capture(PieceKind::Pawn, PieceKind::King));
+
+        let game_state = |fen: &str, has_legal_moves| {
+            let board = Board::from_fen(fen).unwrap();
+            rules.game_state(&board.position(), has_legal_moves)
+        };
+        assert_eq!(BoardState::NotStarted, game_state("4/4/4/4", false));
+        assert_eq!(BoardState::Won, game_state("4/4/4/R3", false));
+        assert_eq!(BoardState::Lost, game_state("4/4/4/R2N", false));
+        assert_eq!(BoardState::InProgress, game_state("4/4/4/R2N", true));
+    }
+
+    #[test]
+    fn test_age_limit() {
+        let rules = Ruleset::with_age_limit(3);
+        assert_eq!(3, rules.max_moves_per_piece);
+        assert!(rules.can_move(2));
+        assert!(!rules.can_move(3));
+
+        let board = Board::from_fen("4/4/4/R[3]2R 3").unwrap();
+        assert_eq!(&rules, board.rules());
+        assert_eq!(1, board.legal_moves.len());
+    }
+
+    // A variant from outside the built in ones, where pawns can not be captured
+    #[derive(Debug)]
+    struct ArmouredPawns;
+
+    impl Variant for ArmouredPawns {
+        fn name(&self) -> String {
+            "armoured".to_string()
+        }
+
+        fn can_capture(&self, _piece: PieceKind, captured: PieceKind) -> bool {
+            captured != PieceKind::Pawn
+        }
+    }
+
+    // Pawns capture pawns only
+    #[derive(Debug)]
+    struct PawnsTakePawns;
+
+    impl Variant for PawnsTakePawns {
+        fn name(&self) -> String {
+            "pawns".to_string()
+        }
+
+        fn can_capture(&self, piece: PieceKind, captured: PieceKind) -> bool {
+            piece != PieceKind::Pawn || captured == PieceKind::Pawn
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        let rules = Ruleset::default().with(ArmouredPawns).with(PawnsTakePawns);
+        assert_eq!(
+            rules,
+            Ruleset::default().with(PawnsTakePawns).with(ArmouredPawns)
+        );
+        assert_ne!(rules, Ruleset::default().with(ArmouredPawns));
+
+        // Adding a variant of the same type replaces it
+        let mut rules = rules.with(ArmouredPawns);
+        assert_eq!(2, rules.variants().len());
+        assert!(!rules.can_capture(PieceKind::Queen, PieceKind::Pawn));
+        assert!(!rules.can_capture(PieceKind::Pawn, PieceKind::Queen));
+        assert!(rules.can_capture(PieceKind::Queen, PieceKind::Rook));
+        assert!(rules.variant::<ArmouredPawns>().is_some());
+
+        rules.remove::<PawnsTakePawns>();
+        assert!(rules.variant::<PawnsTakePawns>().is_none());
+        assert!(rules.can_capture(PieceKind::Pawn, PieceKind::Queen));
+        assert_eq!(1, rules.variants().len());
+    }
+
+    #[test]
+    fn test_custom_variant() {
+        // The rook takes the knight and then the pawn, unless pawns are
+        // armoured
+        let board = Board::from_fen("4/1P2/4/RN2 2").unwrap();
+        assert_eq!(1, board.solution_count());
+
+        let mut armoured = Board::create(BoardOptions {
+            rules: board.rules().clone().with(ArmouredPawns),
+            width: 4,
+            height: 4,
+            ..Default::default()
+        });
+        for (file, ranks) in board.cells.iter().enumerate() {
+            for (rank, piece) in ranks.iter().enumerate() {
+                armoured.set(Square::new(file, rank, 4, *piece));
+            }
+        }
+        assert!(
+            armoured
+                .legal_moves
+                .iter()
+                .all(|mv| mv.to.file != 1 || mv.to.rank != 1)
+        );
+        assert_eq!(0, armoured.solution_count());
+    }
+
+    #[test]
+    fn test_solo_chess() {
+        let rules = Ruleset::solo_chess(2);
+        assert!(!rules.can_capture(PieceKind::Queen, PieceKind::King));
+        assert!(rules.can_capture(PieceKind::King, PieceKind::Queen));
+        assert_eq!(vec![PieceKind::King], rules.required_pieces());
+
+        let is_won = |fen: &str| rules.is_won(&Board::from_fen(fen).unwrap().position());
+        assert!(is_won("4/4/4/K3"));
+        assert!(!is_won("4/4/4/R3"));
+        assert!(!is_won("4/4/4/KR2"));
+    }
+
+    #[test]
+    fn test_target_survivor() {
+        // The king on b2 is the target, so the queen can not take it
+        let board = Board::from_fen("4/4/1K*Q1/4 2").unwrap();
+        assert!(board.position().target() != 0);
+        assert_eq!(1, board.legal_moves.len());
+        assert_eq!(1, board.solution_count());
+
+        let mut board = Board::from_fen("4/4/4/R2R 2 target=a1").unwrap();
+        assert_eq!(1, board.solution_count());
+        let mv = board.solve_first().unwrap().remove(0);
+        assert_eq!("RxRa1", mv.notation());
+        board.make_move(mv);
+        assert_eq!(BoardState::Won, board.game_state);
+
+        let board = Board::from_fen("4/4/4/R2R 2 target=b1").unwrap();
+        assert_eq!(0, board.solution_count());
+    }
+
+    #[test]
+    fn test_promotion() {
+        assert!(Ruleset::default().promotions().is_empty());
+
+        let rules = Ruleset::default().with(Promotion::To(PieceKind::Knight));
+        assert_eq!(&[PieceKind::Knight], rules.promotions());
+
+        // The pawn on b3 is stuck on a4 without promotion, as a queen it can
+        // go on to take the rook on d1
+        let board = Board::from_fen("N3/1P2/4/3R 2").unwrap();
+        assert_eq!(0, board.solution_count());
+        let board = Board::from_fen("N3/1P2/4/3R 2 promote=Q").unwrap();
+        assert_eq!(1, board.solution_count());
+
+        // With a choice, both the queen and the bishop reach d1 from a4
+        let board = Board::from_fen("N3/1P2/4/3R 2 promote").unwrap();
+        assert_eq!(4, board.legal_moves.len());
+        assert_eq!(2, board.solution_count());
+    }
+
+    #[test]
+    #[should_panic(expected = "Pawns can not promote to a king in Solo Chess")]
+    fn test_promotion_to_royal_king() {
+        let _ = Ruleset::solo_chess(2).with(Promotion::To(PieceKind::King));
+    }
+
+    #[test]
+    fn test_chameleon() {
+        let rules = Ruleset::default().with(Chameleon);
+        assert_eq!(
+            PieceKind::Knight,
+            rules.kind_after_capture(PieceKind::Rook, PieceKind::Knight)
+        );
+        let rules = rules.with(RoyalKing);
+        assert_eq!(
+            PieceKind::King,
+            rules.kind_after_capture(PieceKind::King, PieceKind::Knight)
+        );
+
+        // Only a rook that turned into a knight on b4 reaches the pawn on d3
+        let board = Board::from_fen("RN2/3P/4/4 2").unwrap();
+        assert_eq!(0, board.solution_count());
+        let mut board = Board::from_fen("RN2/3P/4/4 2 chameleon").unwrap();
+        assert_eq!(1, board.solution_count());
+
+        for mv in board.solve_first().unwrap() {
+            board.make_move(mv);
+        }
+        assert_eq!(BoardState::Won, board.game_state);
+        assert_eq!(Some(PieceKind::Pawn), board.cells[3][1].map(|p| p.kind));
+
+        board.unmake_move();
+        assert_eq!(Some(PieceKind::Knight), board.cells[1][0].map(|p| p.kind));
+        board.unmake_move();
+        assert_eq!(Some(PieceKind::Rook), board.cells[0][0].map(|p| p.kind));
+
+        // A pawn that takes a knight on the top rank becomes the knight
+        // rather than promoting
+        let board = Board::from_fen("N3/1P2/4/3R 2 promote chameleon").unwrap();
+        assert_eq!(1, board.legal_moves.len());
+        assert_eq!(0, board.solution_count());
+    }
+
+    #[test]
+    fn test_chain_capture() {
+        // R . . N
+        // . . . .
+        // . . . .
+        // B . . P
+        // Any piece may start, but then the capturing piece has to go on
+        let mut board = Board::from_fen("R2N/4/4/B2P 3 chain").unwrap();
+        assert!(board.rules().chain_capture());
+        assert_eq!(
+            Board::from_fen("R2N/4/4/B2P 3").unwrap().legal_moves.len(),
+            board.legal_moves.len()
+        );
+
+        board.make_move(CMove::parse(&board, "RxNd4").unwrap());
+        assert!(
+            board
+                .legal_moves
+                .iter()
+                .all(|mv| mv.from.notation() == "Rd4")
+        );
+        assert_eq!(1, board.legal_moves.len());
+
+        board.unmake_move();
+        assert!(
+            board
+                .legal_moves
+                .iter()
+                .any(|mv| mv.from_piece.kind == PieceKind::Bishop)
+        );
+        assert!(
+            board.solution_count() < Board::from_fen("R2N/4/4/B2P 3").unwrap().solution_count()
+        );
+    }
+}
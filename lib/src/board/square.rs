@@ -1,6 +1,6 @@
 use crate::board::piece::Piece;
 
-use super::constants::BOARD_SIZE;
+use super::errors::SError;
 use super::piece::PieceKind;
 use core::fmt;
 
@@ -9,54 +9,54 @@ pub struct Square {
     // a = 0, b = 1, c = 2, d = 3 and so on.
     pub file: usize,
 
-    // 1 = 0, 2 = 1, 3 = 2, 4 = 3 and so on.
+    // Counted from the top of the board. On a board with 4 ranks,
+    // 4 = 0, 3 = 1, 2 = 2, 1 = 3.
     pub rank: usize,
 
     pub piece: Option<Piece>,
-}
 
-pub struct SquarePair {
-    pub start: Square,
-    pub end: Square,
-    pub dx: usize,
-    pub dy: usize,
-    pub x_dir: i8,
-    pub y_dir: i8,
+    // Number of ranks on the board, needed to translate `rank` to notation.
+    pub height: usize,
 }
 
 impl Square {
-    pub fn new(file: usize, rank: usize, piece: Option<Piece>) -> Self {
-        Square { file, rank, piece }
+    pub fn new(file: usize, rank: usize, height: usize, piece: Option<Piece>) -> Self {
+        Square {
+            file,
+            rank,
+            piece,
+            height,
+        }
     }
 
-    pub fn parse(notation: &str) -> Self {
+    /// Parses a square like `Ka1` or `.c3` on a board with `width` files and
+    /// `height` ranks.
+    pub fn parse(notation: &str, width: usize, height: usize) -> Result<Self, SError> {
+        let invalid = || SError::InvalidSquare(notation.to_string());
         let mut chars = notation.chars();
-        let piece_kind = chars.next().expect("Piece missing");
-        let piece_kind = PieceKind::parse(&piece_kind.to_string());
-        let file = chars.next().expect("File missing");
-        let file = match file {
-            'a' => 0,
-            'b' => 1,
-            'c' => 2,
-            'd' => 3,
-            _ => panic!("file should be between a-d"),
-        };
-
-        let rank = chars.next().unwrap().to_digit(10).expect("rank missing") as usize;
-        if rank < 1 || rank > BOARD_SIZE {
-            panic!("rank should be between 1-{}", BOARD_SIZE);
+        let piece_kind = chars.next().ok_or_else(invalid)?;
+        let piece_kind = PieceKind::parse(&piece_kind.to_string())?;
+        let file = chars.next().ok_or_else(invalid)?;
+        let file = (file as usize)
+            .checked_sub('a' as usize)
+            .filter(|file| *file < width)
+            .ok_or_else(invalid)?;
+
+        let rank: usize = chars.as_str().parse().map_err(|_| invalid())?;
+        if rank < 1 || rank > height {
+            return Err(invalid());
         }
-        let rank = BOARD_SIZE - rank;
-        let piece = piece_kind.map(|kind| Piece::new(kind));
-        Square::new(file, rank, piece)
+        let rank = height - rank;
+        let piece = piece_kind.map(Piece::new);
+        Ok(Square::new(file, rank, height, piece))
     }
 
     pub fn file_notation(&self) -> String {
-        String::from("abcd".chars().nth(self.file).unwrap())
+        String::from((b'a' + self.file as u8) as char)
     }
 
     pub fn rank_notation(&self) -> String {
-        format!("{}", BOARD_SIZE - self.rank)
+        format!("{}", self.height - self.rank)
     }
 
     pub fn notation(&self) -> String {
@@ -64,7 +64,7 @@ impl Square {
             "{}{}{}",
             self.piece_notation(),
             self.file_notation(),
-            BOARD_SIZE - self.rank
+            self.rank_notation()
         )
     }
 
@@ -73,49 +73,11 @@ impl Square {
     }
 
     fn piece_notation(&self) -> String {
-        if self.piece.is_none() {
-            "".to_string()
-        } else {
-            self.piece.unwrap().kind.notation()
-        }
-    }
-}
-
-impl SquarePair {
-    pub fn new(start: Square, end: Square) -> Self {
-        let mut dx = 0;
-        let mut dy = 0;
-        let mut x_dir = 0;
-        let mut y_dir = 0;
-        if start.file > end.file {
-            x_dir = -1;
-            dx = start.file - end.file;
-        } else if end.file > start.file {
-            x_dir = 1;
-            dx = end.file - start.file;
-        }
-
-        if start.rank > end.rank {
-            y_dir = -1;
-            dy = start.rank - end.rank;
-        } else if end.rank > start.rank {
-            y_dir = 1;
-            dy = end.rank - start.rank;
-        }
-
-        SquarePair {
-            start,
-            end,
-            dx,
-            dy,
-            x_dir,
-            y_dir,
+        match self.piece {
+            Some(piece) => piece.kind.notation(),
+            None => "".to_string(),
         }
     }
-
-    pub fn is_different(&self) -> bool {
-        self.dx != 0 || self.dy != 0
-    }
 }
 
 impl fmt::Debug for Square {
@@ -131,7 +93,7 @@ mod tests {
     macro_rules! validate_square {
         ($notation:literal, $file:expr, $rank:expr) => {
             let notation = format!("{}{}", "K", $notation);
-            let square = Square::parse(&notation);
+            let square = Square::parse(&notation, 4, 4).unwrap();
             assert_eq!(square.file, $file);
             assert_eq!(square.rank, $rank);
             assert_eq!(square.piece.unwrap().kind, PieceKind::King);
@@ -158,4 +120,37 @@ mod tests {
         validate_square!("d3", 3, 1);
         validate_square!("d4", 3, 0);
     }
+
+    #[test]
+    fn test_square_parse_tall_board() {
+        let square = Square::parse("Nh8", 8, 8).unwrap();
+        assert_eq!(square.file, 7);
+        assert_eq!(square.rank, 0);
+        assert_eq!(square.notation(), "Nh8");
+
+        let square = Square::parse(".e5", 5, 6).unwrap();
+        assert_eq!(square.file, 4);
+        assert_eq!(square.rank, 1);
+        assert!(square.piece.is_none());
+        assert_eq!(square.notation(), "e5");
+    }
+
+    #[test]
+    fn test_square_parse_invalid() {
+        let invalid = |notation: &str| Err(SError::InvalidSquare(notation.to_string()));
+        assert_eq!(invalid(""), Square::parse("", 4, 4));
+        assert_eq!(invalid("K"), Square::parse("K", 4, 4));
+        assert_eq!(invalid("Kz1"), Square::parse("Kz1", 4, 4));
+        assert_eq!(invalid("KA1"), Square::parse("KA1", 4, 4));
+        assert_eq!(invalid("Ka"), Square::parse("Ka", 4, 4));
+        assert_eq!(invalid("Ka0"), Square::parse("Ka0", 4, 4));
+        assert_eq!(invalid("Ka5"), Square::parse("Ka5", 4, 4));
+        assert_eq!(invalid("Ke1"), Square::parse("Ke1", 4, 4));
+        assert_eq!(invalid("Kc1"), Square::parse("Kc1", 2, 4));
+        assert_eq!(invalid("Ka1x"), Square::parse("Ka1x", 4, 4));
+        assert_eq!(
+            Err(SError::InvalidPiece("X".to_string())),
+            Square::parse("Xa1", 4, 4)
+        );
+    }
 }
@@ -1,11 +1,24 @@
 use std::fmt::Display;
 
-use crate::board::{
-    Board, BoardOptions,
-    cmove::CMove,
-    piece::{Piece, PieceKind},
+use crate::{
+    board::{
+        Board, BoardOptions,
+        cmove::CMove,
+        piece::{Piece, PieceKind},
+        position::Position,
+        ruleset::{Ruleset, TargetSquare},
+        square::Square,
+    },
+    solver::{
+        Solver,
+        budget::{BudgetExhausted, SearchBudget},
+        difficulty::{Difficulty, DifficultyBucket},
+    },
 };
 
+// Changes tried on a generated puzzle before starting over from a new one
+const CLIMB_STEPS: u32 = 40;
+
 pub trait RandomRange {
     fn gen_range(&self, min: usize, max: usize) -> usize;
 }
@@ -17,21 +30,165 @@ pub struct Puzzle {
     pub solved: bool,
 }
 
+/// What the last piece standing of a generated puzzle has to be
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TargetSurvivor {
+    /// Any piece left alone on the board wins
+    #[default]
+    Any,
+
+    /// The piece that survives one of the solutions, it can not be captured
+    Piece,
+
+    /// The square one of the solutions ends on
+    Square,
+}
+
+/// How many solutions a generated puzzle may have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SolutionBounds {
+    pub min: u32,
+    pub max: u32,
+
+    /// Count capture orders that leave the same last piece on the same
+    /// square as one solution
+    pub merge_orders: bool,
+}
+
+/// The difficulty scores a puzzle from `generate_with_difficulty` has to
+/// fall between. Like the buckets of `DifficultyBucket::from_score`, a
+/// target holds its lowest score but not its highest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DifficultyTarget {
+    pub min_score: f64,
+    pub max_score: f64,
+
+    /// Number of random puzzles to start hill climbing from
+    pub attempts: u32,
+}
+
 pub struct GenerateStats {
     piece_total: u32,
     piece_success: u32,
     total: u32,
     board: Option<Board>,
     solutions: Vec<Vec<CMove>>,
+    nodes: u64,
+
+    // Candidates with a number of solutions outside of the bounds
+    too_few_solutions: u32,
+    too_many_solutions: u32,
+
+    // Candidates rated outside of the targeted difficulty, and the rating of
+    // the generated puzzle
+    too_easy: u32,
+    too_hard: u32,
+    difficulty: Option<Difficulty>,
+
+    // The candidate rated closest to the targeted difficulty, kept for when
+    // none is rated within it
+    closest: Option<(Board, Difficulty)>,
+
+    // Set when generation stopped because the budget ran out, rather than
+    // because every attempt failed
+    pub budget_exhausted: bool,
+}
+
+/// How the pieces of a generated puzzle are put on the board
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Random pieces on random squares, each one kept only if the board
+    /// stays solvable
+    #[default]
+    WeightedRandom,
+
+    /// Backwards from the last piece standing, undoing one capture at a time,
+    /// so that every board is solvable by construction
+    Retrograde,
+}
+
+// Tries at placing or uncapturing a piece before starting over
+const PLACEMENT_ATTEMPTS: u32 = 15;
+
+impl Strategy {
+    pub fn generate(
+        &self,
+        num_pieces: u32,
+        solutions: SolutionBounds,
+        board_options: BoardOptions,
+        budget: SearchBudget,
+        rand: &impl RandomRange,
+    ) -> GenerateStats {
+        match self {
+            Strategy::WeightedRandom => {
+                generate_weighted_random(num_pieces, solutions, board_options, budget, rand)
+            }
+            Strategy::Retrograde => {
+                generate_retrograde(num_pieces, solutions, board_options, budget, rand)
+            }
+        }
+    }
 }
 
 pub fn generate_weighted_random(
     num_pieces: u32,
-    num_solutions: u32,
-    max_moves_per_piece: u32,
+    solutions: SolutionBounds,
+    board_options: BoardOptions,
+    budget: SearchBudget,
     rand: &impl RandomRange,
 ) -> GenerateStats {
-    let candidate_pieces = vec![
+    generate_with(
+        num_pieces,
+        solutions,
+        board_options,
+        budget,
+        rand,
+        try_generate,
+    )
+}
+
+/// Generates puzzles backwards: a single piece is put on the board, and then
+/// captures are undone one at a time by moving a piece back to a square it
+/// could have captured from and putting a new piece where it stood. Pieces
+/// only undo as many captures as the age limit lets them make. Every capture
+/// is undone the way the rules would have it played: a chameleon goes back
+/// as the piece it was before and leaves the piece it took behind, a pawn
+/// that would have promoted is never left a pawn, and with chain captures
+/// the piece that was moved back last undoes the next capture.
+pub fn generate_retrograde(
+    num_pieces: u32,
+    solutions: SolutionBounds,
+    board_options: BoardOptions,
+    budget: SearchBudget,
+    rand: &impl RandomRange,
+) -> GenerateStats {
+    generate_with(
+        num_pieces,
+        solutions,
+        board_options,
+        budget,
+        rand,
+        try_retrograde,
+    )
+}
+
+/// Makes `attempt`s at a puzzle until one succeeds or the budget runs out
+fn generate_with<R: RandomRange>(
+    num_pieces: u32,
+    solutions: SolutionBounds,
+    board_options: BoardOptions,
+    budget: SearchBudget,
+    rand: &R,
+    attempt: fn(
+        u32,
+        SolutionBounds,
+        &BoardOptions,
+        SearchBudget,
+        &R,
+        Vec<PieceKind>,
+    ) -> GenerateStats,
+) -> GenerateStats {
+    let mut candidate_pieces = vec![
         PieceKind::Pawn,
         PieceKind::Pawn,
         PieceKind::Pawn,
@@ -47,36 +204,283 @@ pub fn generate_weighted_random(
         PieceKind::Rook,
         PieceKind::Rook,
     ];
+    // Puzzles with more pieces than that draw from repeats of the same mix
+    let mix = candidate_pieces.len();
+    while candidate_pieces.len() < num_pieces as usize {
+        candidate_pieces.extend_from_within(..mix);
+    }
 
-    if num_pieces > candidate_pieces.len().try_into().unwrap() {
+    let num_squares =
+        board_options.width * board_options.height - board_options.blocked.count_ones() as usize;
+    if num_pieces as usize > num_squares {
         panic!(
             "Number of pieces to place on the board should be <= {}",
-            candidate_pieces.len()
+            num_squares
         );
     }
 
     let attempts: u32 = 1000;
     let mut overall_stats = GenerateStats::new(0, 0, 0, None, vec![]);
     for _ in 0..attempts {
-        let stats = try_generate(
+        let stats = attempt(
             num_pieces,
-            num_solutions,
-            max_moves_per_piece,
+            solutions,
+            &board_options,
+            budget.remaining(overall_stats.nodes),
             rand,
             candidate_pieces.clone(),
         );
         overall_stats.piece_total += stats.piece_total;
         overall_stats.piece_success += stats.piece_success;
         overall_stats.total += stats.total;
+        overall_stats.nodes += stats.nodes;
+        overall_stats.too_few_solutions += stats.too_few_solutions;
+        overall_stats.too_many_solutions += stats.too_many_solutions;
         overall_stats.board = stats.board;
-        if overall_stats.board.is_some() {
+        overall_stats.solutions = stats.solutions;
+        overall_stats.budget_exhausted = stats.budget_exhausted;
+        if overall_stats.board.is_some() || overall_stats.budget_exhausted {
+            return overall_stats;
+        }
+    }
+
+    overall_stats
+}
+
+/// Generates puzzles with `strategy` until one is rated within `target`.
+/// Every puzzle that is not is hill climbed towards the target first, by
+/// moving single pieces to empty squares and by swapping pairs of pieces,
+/// keeping every change that does not take it further away.
+pub fn generate_with_difficulty(
+    num_pieces: u32,
+    solutions: SolutionBounds,
+    board_options: BoardOptions,
+    strategy: Strategy,
+    target: DifficultyTarget,
+    budget: SearchBudget,
+    rand: &impl RandomRange,
+) -> GenerateStats {
+    let mut overall_stats = GenerateStats::new(0, 0, 0, None, vec![]);
+    for _ in 0..target.attempts {
+        let stats = strategy.generate(
+            num_pieces,
+            solutions,
+            board_options.clone(),
+            budget.remaining(overall_stats.nodes),
+            rand,
+        );
+        overall_stats.piece_total += stats.piece_total;
+        overall_stats.piece_success += stats.piece_success;
+        overall_stats.total += stats.total;
+        overall_stats.nodes += stats.nodes;
+        overall_stats.too_few_solutions += stats.too_few_solutions;
+        overall_stats.too_many_solutions += stats.too_many_solutions;
+        if stats.budget_exhausted {
+            overall_stats.budget_exhausted = true;
             return overall_stats;
         }
+
+        let Some(board) = stats.board else {
+            continue;
+        };
+
+        let mut solver = Solver::with_budget(&board, budget.remaining(overall_stats.nodes));
+        let climbed = climb(
+            board,
+            solutions,
+            &target,
+            &mut solver,
+            rand,
+            &mut overall_stats,
+        );
+        overall_stats.nodes += solver.nodes();
+        let found = match climbed {
+            Ok(found) => found,
+            Err(_) => {
+                overall_stats.budget_exhausted = true;
+                return overall_stats;
+            }
+        };
+
+        let Some((board, difficulty)) = found else {
+            continue;
+        };
+
+        let mut solver = Solver::with_budget(&board, budget.remaining(overall_stats.nodes));
+        let solved = solver.solve(&board);
+        overall_stats.nodes += solver.nodes();
+        let Ok(puzzle) = solved else {
+            overall_stats.budget_exhausted = true;
+            return overall_stats;
+        };
+
+        overall_stats.board = Some(puzzle.board);
+        overall_stats.solutions = puzzle.solutions;
+        overall_stats.difficulty = Some(difficulty);
+        overall_stats.closest = None;
+        return overall_stats;
     }
 
     overall_stats
 }
 
+/// Changes `board` a step at a time for as long as that brings its rating
+/// closer to `target`, returns the first board rated within it
+fn climb(
+    mut board: Board,
+    solutions: SolutionBounds,
+    target: &DifficultyTarget,
+    solver: &mut Solver,
+    rand: &impl RandomRange,
+    stats: &mut GenerateStats,
+) -> Result<Option<(Board, Difficulty)>, BudgetExhausted> {
+    let mut distance = f64::MAX;
+    for step in 0..=CLIMB_STEPS {
+        let candidate = match step {
+            0 => board.clone(),
+            _ => mutate(&board, rand),
+        };
+
+        let solution_count = solutions.count(solver, &candidate.position())?;
+        if solution_count == 0 {
+            continue;
+        }
+
+        if !solutions.contains(solution_count) {
+            match solution_count < solutions.min as u64 {
+                true => stats.too_few_solutions += 1,
+                false => stats.too_many_solutions += 1,
+            }
+            continue;
+        }
+
+        let difficulty = solver.rate(&candidate)?;
+        if target.contains(difficulty.score) {
+            return Ok(Some((candidate, difficulty)));
+        }
+
+        if difficulty.score < target.min_score {
+            stats.too_easy += 1;
+        } else {
+            stats.too_hard += 1;
+        }
+
+        let candidate_distance = target.distance(difficulty.score);
+        let closest_distance = match &stats.closest {
+            Some((_, closest)) => target.distance(closest.score),
+            None => f64::MAX,
+        };
+        if candidate_distance < closest_distance {
+            stats.closest = Some((candidate.clone(), difficulty));
+        }
+
+        if candidate_distance <= distance {
+            board = candidate;
+            distance = candidate_distance;
+        }
+    }
+
+    Ok(None)
+}
+
+/// A copy of `board` with one piece moved to an empty square or with two
+/// pieces swapped
+fn mutate(board: &Board, rand: &impl RandomRange) -> Board {
+    let mut board = board.clone();
+    let occupied = board.occupied_squares();
+    let empty = board.empty_squares();
+    let mut from = occupied[rand.gen_range(0, occupied.len())].clone();
+    let mut to = match rand.gen_range(0, 2) {
+        0 if !empty.is_empty() => empty[rand.gen_range(0, empty.len())].clone(),
+        _ => occupied[rand.gen_range(0, occupied.len())].clone(),
+    };
+
+    std::mem::swap(&mut from.piece, &mut to.piece);
+    board.set(from);
+    board.set(to);
+    board
+}
+
+impl SolutionBounds {
+    pub fn new(min: u32, max: u32) -> Self {
+        SolutionBounds {
+            min,
+            max,
+            merge_orders: false,
+        }
+    }
+
+    pub fn at_most(max: u32) -> Self {
+        SolutionBounds::new(1, max)
+    }
+
+    /// Exactly one solution
+    pub fn unique() -> Self {
+        SolutionBounds::new(1, 1)
+    }
+
+    pub fn contains(&self, solution_count: u64) -> bool {
+        (self.min as u64..=self.max as u64).contains(&solution_count)
+    }
+
+    /// Counts the solutions from `position` the way these bounds do, up to
+    /// one more than the maximum
+    fn count(&self, solver: &mut Solver, position: &Position) -> Result<u64, BudgetExhausted> {
+        let limit = self.max as u64 + 1;
+        match self.merge_orders {
+            true => solver.count_endings(position, limit),
+            false => solver.count_solutions(position, limit),
+        }
+    }
+}
+
+impl Display for SolutionBounds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.min == self.max {
+            true => write!(f, "exactly {}", self.max)?,
+            false => write!(f, "{} to {}", self.min, self.max)?,
+        }
+
+        if self.merge_orders {
+            write!(f, " distinct")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DifficultyTarget {
+    pub fn new(min_score: f64, max_score: f64) -> Self {
+        DifficultyTarget {
+            min_score,
+            max_score,
+            attempts: 20,
+        }
+    }
+
+    /// The scores of `bucket`
+    pub fn bucket(bucket: DifficultyBucket) -> Self {
+        let (min_score, max_score) = bucket.score_range();
+        DifficultyTarget::new(min_score, max_score)
+    }
+
+    pub fn contains(&self, score: f64) -> bool {
+        (self.min_score..self.max_score).contains(&score)
+    }
+
+    /// How far `score` is from the target, 0 at its highest score even
+    /// though the target does not hold it
+    fn distance(&self, score: f64) -> f64 {
+        if score < self.min_score {
+            self.min_score - score
+        } else if score > self.max_score {
+            score - self.max_score
+        } else {
+            0.0
+        }
+    }
+}
+
 impl GenerateStats {
     fn new(
         piece_total: u32,
@@ -91,24 +495,100 @@ impl GenerateStats {
             total,
             board,
             solutions,
+            nodes: 0,
+            too_few_solutions: 0,
+            too_many_solutions: 0,
+            too_easy: 0,
+            too_hard: 0,
+            difficulty: None,
+            closest: None,
+            budget_exhausted: false,
+        }
+    }
+
+    fn exhausted(piece_total: u32, piece_success: u32, nodes: u64) -> Self {
+        Self {
+            nodes,
+            budget_exhausted: true,
+            ..Self::new(piece_total, piece_success, 1, None, vec![])
         }
     }
 
+    fn with_nodes(mut self, nodes: u64) -> Self {
+        self.nodes = nodes;
+        self
+    }
+
+    /// Number of positions searched over every attempt
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Number of candidates with fewer solutions than the bounds allow
+    pub fn too_few_solutions(&self) -> u32 {
+        self.too_few_solutions
+    }
+
+    /// Number of candidates with more solutions than the bounds allow
+    pub fn too_many_solutions(&self) -> u32 {
+        self.too_many_solutions
+    }
+
+    /// Number of candidates that were rated easier than the target
+    pub fn too_easy(&self) -> u32 {
+        self.too_easy
+    }
+
+    /// Number of candidates that were rated harder than the target
+    pub fn too_hard(&self) -> u32 {
+        self.too_hard
+    }
+
+    /// The rating of the generated puzzle, when a difficulty was targeted
+    pub fn difficulty(&self) -> Option<&Difficulty> {
+        self.difficulty.as_ref()
+    }
+
+    /// When no puzzle was rated within the targeted difficulty, the
+    /// candidate that came closest along with its rating
+    pub fn closest(&self) -> Option<(&Board, &Difficulty)> {
+        self.closest
+            .as_ref()
+            .map(|(board, difficulty)| (board, difficulty))
+    }
+
     pub fn print_stats(&self) {
         let mut stats = String::new();
         add_stat(&mut stats, "Total attempts", self.total);
         add_stat(&mut stats, "Total pieces placed", self.piece_total);
         add_stat(&mut stats, "Success pieces placed", self.piece_success);
+        add_stat(&mut stats, "Positions searched", self.nodes);
+        add_stat(&mut stats, "Too few solutions", self.too_few_solutions);
+        add_stat(&mut stats, "Too many solutions", self.too_many_solutions);
+        if self.too_easy + self.too_hard > 0 || self.difficulty.is_some() {
+            add_stat(&mut stats, "Rejected as too easy", self.too_easy);
+            add_stat(&mut stats, "Rejected as too hard", self.too_hard);
+        }
+        if let Some(difficulty) = &self.difficulty {
+            add_stat(&mut stats, "Difficulty", format!("{:.1}", difficulty.score));
+        }
+        if let Some((_, closest)) = &self.closest {
+            add_stat(
+                &mut stats,
+                "Closest difficulty",
+                format!("{:.1}", closest.score),
+            );
+        }
+        if self.budget_exhausted {
+            add_stat(&mut stats, "Budget exhausted", "yes");
+        }
 
         println!("{}", stats);
     }
 
     pub fn puzzle(self) -> Option<Puzzle> {
-        let Some(board) = self.board else {
-            return None;
-        };
-
-        let solved = self.solutions.len() > 0;
+        let board = self.board?;
+        let solved = !self.solutions.is_empty();
 
         Some(Puzzle {
             board,
@@ -118,6 +598,77 @@ impl GenerateStats {
     }
 }
 
+/// Turns a generated puzzle into a target survivor puzzle. The survivor of a
+/// random solution becomes the target, so the puzzle stays solvable while
+/// the solutions that end elsewhere stop counting. Survivors that leave fewer
+/// solutions than `solutions` allows are passed over for the survivors of
+/// the other solutions, and `None` is returned when every one of them is.
+pub fn designate_target(
+    puzzle: Puzzle,
+    target: TargetSurvivor,
+    solutions: SolutionBounds,
+    budget: SearchBudget,
+    rand: &impl RandomRange,
+) -> Result<Option<Puzzle>, BudgetExhausted> {
+    if target == TargetSurvivor::Any || puzzle.solutions.is_empty() {
+        return Ok(Some(puzzle));
+    }
+
+    let mut nodes = 0;
+    let first = rand.gen_range(0, puzzle.solutions.len());
+    for index in 0..puzzle.solutions.len() {
+        let solution = &puzzle.solutions[(first + index) % puzzle.solutions.len()];
+        let Some(board) = with_target(&puzzle.board, solution, target) else {
+            return Ok(Some(puzzle));
+        };
+
+        let mut solver = Solver::with_budget(&board, budget.remaining(nodes));
+        let solution_count = solutions.count(&mut solver, &board.position());
+        nodes += solver.nodes();
+        if solution_count? >= solutions.min as u64 {
+            return solver.solve(&board).map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+/// `board` with the survivor of `solution` as the target, `None` for a
+/// solution without moves
+fn with_target(board: &Board, solution: &[CMove], target: TargetSurvivor) -> Option<Board> {
+    let last = solution.last()?;
+    let mut board = board.clone();
+    match target {
+        TargetSurvivor::Piece => {
+            // Follow the survivor back to the square it started on
+            let mut square = &last.to;
+            for mv in solution.iter().rev() {
+                if mv.to.file == square.file && mv.to.rank == square.rank {
+                    square = &mv.from;
+                }
+            }
+
+            let mut square = square.clone();
+            square.piece = board.cells[square.file][square.rank].map(|piece| Piece {
+                target: true,
+                ..piece
+            });
+            board.set(square);
+        }
+        _ => {
+            let target_square = last.to.rank * board.width + last.to.file;
+            board.set_rules(
+                board
+                    .rules()
+                    .clone()
+                    .with(TargetSquare(target_square as u8)),
+            );
+        }
+    }
+
+    Some(board)
+}
+
 fn add_stat<T>(stats: &mut String, name: &str, val: T)
 where
     T: Display,
@@ -127,40 +678,55 @@ where
 
 fn try_generate(
     num_pieces: u32,
-    num_solutions: u32,
-    max_moves_per_piece: u32,
+    solutions: SolutionBounds,
+    board_options: &BoardOptions,
+    budget: SearchBudget,
     rand: &impl RandomRange,
     mut candidate_pieces: Vec<PieceKind>,
 ) -> GenerateStats {
-    let mut board = Board::create(BoardOptions {
-        max_moves_per_piece,
-    });
+    let mut board = Board::create(board_options.clone());
+    let mut solver = Solver::with_budget(&board, budget);
+
+    // Pieces that the rules require are placed before any random ones
+    let mut required_pieces = board_options.rules.required_pieces();
 
     let mut piece_total = 0;
     let mut piece_success = 0;
     for _ in 0..num_pieces {
         let mut placed = false;
         let empty_squares = board.empty_squares();
-        let mut attempts = 15;
+        let mut attempts = PLACEMENT_ATTEMPTS;
         while !placed {
             if attempts == 0 {
-                return GenerateStats::new(piece_total, piece_success, 1, None, vec![]);
+                return GenerateStats::new(piece_total, piece_success, 1, None, vec![])
+                    .with_nodes(solver.nodes());
             }
 
             attempts -= 1;
             piece_total += 1;
 
-            let index = rand.gen_range(0, candidate_pieces.len());
-            let piece_kind = candidate_pieces[index];
+            let (piece_kind, index) = match required_pieces.last() {
+                Some(kind) => (*kind, None),
+                None => {
+                    let index = rand.gen_range(0, candidate_pieces.len());
+                    (candidate_pieces[index], Some(index))
+                }
+            };
             let square_index = rand.gen_range(0, empty_squares.len());
             let mut random_square = empty_squares[square_index].clone();
             random_square.piece = Some(Piece::new(piece_kind));
             board.set(random_square.clone());
-            let puzzle = board.solve();
-            if puzzle.solutions.len() > 0 {
+            let Ok(solvable) = solver.is_solvable(&board.position()) else {
+                return GenerateStats::exhausted(piece_total, piece_success, solver.nodes());
+            };
+
+            if solvable {
                 placed = true;
                 piece_success += 1;
-                candidate_pieces.remove(index);
+                match index {
+                    Some(index) => _ = candidate_pieces.remove(index),
+                    None => _ = required_pieces.pop(),
+                }
                 continue;
             }
 
@@ -169,23 +735,204 @@ fn try_generate(
         }
     }
 
-    let puzzle = board.solve();
-    if puzzle.solutions.len() > num_solutions as usize {
-        GenerateStats::new(piece_total, piece_success, 1, None, vec![])
-    } else {
-        GenerateStats::new(
-            piece_total,
-            piece_success,
-            1,
-            Some(puzzle.board),
-            puzzle.solutions,
-        )
+    check_solutions(board, solutions, &mut solver, piece_total, piece_success)
+}
+
+fn try_retrograde(
+    num_pieces: u32,
+    solutions: SolutionBounds,
+    board_options: &BoardOptions,
+    budget: SearchBudget,
+    rand: &impl RandomRange,
+    mut candidate_pieces: Vec<PieceKind>,
+) -> GenerateStats {
+    let mut board = Board::create(board_options.clone());
+    let tables = board.tables();
+    let rules = &board_options.rules;
+    let mut required_pieces = rules.required_pieces();
+
+    // Captures that the piece on each square makes in the solution so far
+    let mut captures_made = vec![0; board.width * board.height];
+
+    // With chain captures one piece makes every capture, the one that was
+    // moved back last
+    let mut chained = None;
+
+    // The last piece standing is the required piece, if there is one
+    let survivor = match required_pieces.pop() {
+        Some(kind) => kind,
+        None => candidate_pieces.remove(rand.gen_range(0, candidate_pieces.len())),
+    };
+    let mut square = match rules.target_square() {
+        Some(sq) => {
+            // No piece can survive on a blocked target square
+            let (file, rank) = tables.coordinates(sq as usize);
+            if board.is_blocked(file, rank) {
+                return GenerateStats::new(0, 0, 1, None, vec![]);
+            }
+            Square::new(file, rank, board.height, None)
+        }
+        None => {
+            let empty_squares = board.empty_squares();
+            empty_squares[rand.gen_range(0, empty_squares.len())].clone()
+        }
+    };
+    square.piece = Some(Piece::new(survivor));
+    board.set(square);
+
+    let mut piece_total = 0;
+    let mut piece_success = 0;
+    for _ in 1..num_pieces {
+        let mut uncaptured = false;
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            piece_total += 1;
+            let capturers = board
+                .occupied_squares()
+                .into_iter()
+                .filter(|square| {
+                    let sq = tables.index(square.file, square.rank);
+                    chained.is_none_or(|chained| chained == sq) && rules.can_move(captures_made[sq])
+                })
+                .collect::<Vec<_>>();
+            if capturers.is_empty() {
+                break;
+            }
+
+            let mut to = capturers[rand.gen_range(0, capturers.len())].clone();
+            let capturer = to.piece.expect("capturers stand on occupied squares");
+            let (new_kind, index) = match required_pieces.last() {
+                Some(kind) => (*kind, None),
+                None => {
+                    let index = rand.gen_range(0, candidate_pieces.len());
+                    (candidate_pieces[index], Some(index))
+                }
+            };
+
+            // The new piece is either the one that was captured, or the piece
+            // the capturer was before it turned into the piece it captured
+            let to_sq = tables.index(to.file, to.rank);
+            let kinds = [(capturer.kind, new_kind), (new_kind, capturer.kind)]
+                .into_iter()
+                .filter(|(kind, captured)| {
+                    rules.can_capture(*kind, *captured)
+                        && kinds_after_capture(rules, *kind, *captured, to_sq < board.width)
+                            .contains(&capturer.kind)
+                })
+                .collect::<Vec<_>>();
+            if kinds.is_empty() {
+                continue;
+            }
+            let (kind, captured) = kinds[rand.gen_range(0, kinds.len())];
+
+            // The capturer has to reach its square from where it goes back
+            // to, with every other piece in place
+            let occupied = board.position().occupied();
+            let from_squares = board
+                .empty_squares()
+                .into_iter()
+                .filter(|from| {
+                    let from_sq = tables.index(from.file, from.rank);
+                    tables.captures(kind, from_sq, occupied | 1 << from_sq) & 1 << to_sq != 0
+                })
+                .collect::<Vec<_>>();
+            if from_squares.is_empty() {
+                continue;
+            }
+
+            let mut from = from_squares[rand.gen_range(0, from_squares.len())].clone();
+            let from_sq = tables.index(from.file, from.rank);
+            from.piece = Some(Piece { kind, ..capturer });
+            to.piece = Some(Piece::new(captured));
+            board.set(from);
+            board.set(to);
+            captures_made[from_sq] = captures_made[to_sq] + 1;
+            captures_made[to_sq] = 0;
+            if rules.chain_capture() {
+                chained = Some(from_sq);
+            }
+
+            piece_success += 1;
+            match index {
+                Some(index) => _ = candidate_pieces.remove(index),
+                None => _ = required_pieces.pop(),
+            }
+            uncaptured = true;
+            break;
+        }
+
+        if !uncaptured {
+            return GenerateStats::new(piece_total, piece_success, 1, None, vec![]);
+        }
+    }
+
+    // The undone captures are one solution, the solver still counts the others
+    let mut solver = Solver::with_budget(&board, budget);
+    check_solutions(board, solutions, &mut solver, piece_total, piece_success)
+}
+
+/// Every kind a `piece` that captures a `captured` piece can be left as,
+/// more than one when a pawn captures onto the `top_rank` and may promote
+fn kinds_after_capture(
+    rules: &Ruleset,
+    piece: PieceKind,
+    captured: PieceKind,
+    top_rank: bool,
+) -> Vec<PieceKind> {
+    let kind = rules.kind_after_capture(piece, captured);
+    match piece == PieceKind::Pawn && kind == PieceKind::Pawn && top_rank {
+        true if !rules.promotions().is_empty() => rules.promotions().to_vec(),
+        _ => vec![kind],
+    }
+}
+
+/// Finishes an attempt at `board` by checking that its number of solutions
+/// is within `solutions`
+fn check_solutions(
+    board: Board,
+    solutions: SolutionBounds,
+    solver: &mut Solver,
+    piece_total: u32,
+    piece_success: u32,
+) -> GenerateStats {
+    let Ok(solution_count) = solutions.count(solver, &board.position()) else {
+        return GenerateStats::exhausted(piece_total, piece_success, solver.nodes());
+    };
+
+    if !solutions.contains(solution_count) {
+        let mut stats = GenerateStats::new(piece_total, piece_success, 1, None, vec![])
+            .with_nodes(solver.nodes());
+        match solution_count < solutions.min as u64 {
+            true => stats.too_few_solutions = 1,
+            false => stats.too_many_solutions = 1,
+        }
+        return stats;
     }
+
+    let Ok(puzzle) = solver.solve(&board) else {
+        return GenerateStats::exhausted(piece_total, piece_success, solver.nodes());
+    };
+
+    GenerateStats::new(
+        piece_total,
+        piece_success,
+        1,
+        Some(puzzle.board),
+        puzzle.solutions,
+    )
+    .with_nodes(solver.nodes())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::board::BoardState;
+    use std::cell::Cell;
+
+    use crate::{
+        board::{
+            BoardState, Topology,
+            ruleset::{ChainCapture, Chameleon, Promotion, Ruleset, TargetSquare, Variant},
+        },
+        solver::budget::CancelToken,
+    };
 
     use super::*;
 
@@ -198,16 +945,600 @@ mod tests {
         }
     }
 
+    /// The same numbers on every run, so that the generated puzzles do not
+    /// change from one run to the next
+    struct SeededRandom(Cell<u64>);
+    impl RandomRange for SeededRandom {
+        fn gen_range(&self, min: usize, max: usize) -> usize {
+            let mut x = self.0.get();
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            self.0.set(x);
+            min + (x % (max - min) as u64) as usize
+        }
+    }
+
+    /// Generates a 5 piece puzzle from a fixed seed, checks that every one of
+    /// its solutions wins when played out and hands it to `check`
+    fn assert_generates(options: BoardOptions, check: impl Fn(&Puzzle)) {
+        assert_generates_with(Strategy::WeightedRandom, options, check);
+    }
+
+    /// Like `assert_generates`, with the puzzle generated by `strategy`
+    fn assert_generates_with(strategy: Strategy, options: BoardOptions, check: impl Fn(&Puzzle)) {
+        let gen_stats = strategy.generate(
+            5,
+            SolutionBounds::at_most(10),
+            options.clone(),
+            SearchBudget::unlimited(),
+            &SeededRandom(Cell::new(0x5eed)),
+        );
+        let puzzle = gen_stats.puzzle().expect("No puzzle was generated");
+        assert!(puzzle.solved);
+        assert_eq!(5, puzzle.board.position().piece_count());
+        assert_eq!(options.rules, *puzzle.board.rules());
+        assert_eq!(options.blocked, puzzle.board.blocked());
+        assert_eq!(options.topology, puzzle.board.topology());
+
+        for solution in &puzzle.solutions {
+            let mut board = puzzle.board.clone();
+            for mv in solution {
+                assert!(board.legal_moves.contains(mv));
+                board.make_move(mv.clone());
+            }
+            assert_eq!(BoardState::Won, board.game_state);
+        }
+
+        check(&puzzle);
+    }
+
     #[test]
     fn generator_smoke() {
         for _ in 0..10 {
-            let gen_stats = generate_weighted_random(5, 5, 10, &TestRandom);
+            let options = BoardOptions {
+                rules: Ruleset::with_age_limit(10),
+                ..Default::default()
+            };
+            let gen_stats = generate_weighted_random(
+                5,
+                SolutionBounds::at_most(5),
+                options,
+                SearchBudget::unlimited(),
+                &TestRandom,
+            );
             let board = gen_stats.board.expect("No puzzle was generated");
             assert_eq!(board.game_state, BoardState::InProgress);
 
             let puzzle = board.solve();
             assert!(puzzle.solutions.len() <= 5);
-            assert!(puzzle.solutions.len() >= 1);
+            assert!(!puzzle.solutions.is_empty());
+        }
+    }
+
+    #[test]
+    fn generator_larger_board() {
+        let options = BoardOptions {
+            width: 5,
+            height: 5,
+            ..Default::default()
+        };
+        let gen_stats = generate_weighted_random(
+            4,
+            SolutionBounds::at_most(100),
+            options,
+            SearchBudget::unlimited(),
+            &TestRandom,
+        );
+        let board = gen_stats.board.expect("No puzzle was generated");
+        assert_eq!(5, board.width);
+        assert_eq!(5, board.height);
+        assert!(board.solve().solved);
+    }
+
+    #[test]
+    fn generator_budget_exhausted() {
+        let options = BoardOptions {
+            width: 6,
+            height: 6,
+            rules: Ruleset::with_age_limit(14),
+            ..Default::default()
+        };
+        let budget = SearchBudget::unlimited().with_max_nodes(10);
+        let gen_stats = generate_weighted_random(
+            14,
+            SolutionBounds::at_most(1),
+            options.clone(),
+            budget,
+            &TestRandom,
+        );
+        assert!(gen_stats.budget_exhausted);
+        assert!(gen_stats.nodes <= 10);
+        assert!(gen_stats.puzzle().is_none());
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let budget = SearchBudget::unlimited().with_cancel_token(cancel);
+        let gen_stats =
+            generate_weighted_random(14, SolutionBounds::at_most(1), options, budget, &TestRandom);
+        assert!(gen_stats.budget_exhausted);
+    }
+
+    #[test]
+    fn generator_more_pieces_than_the_mix() {
+        let options = BoardOptions {
+            width: 8,
+            height: 8,
+            rules: Ruleset::with_age_limit(16),
+            ..Default::default()
+        };
+        for strategy in [Strategy::WeightedRandom, Strategy::Retrograde] {
+            let budget = SearchBudget::unlimited().with_max_nodes(1000);
+            let gen_stats = strategy.generate(
+                16,
+                SolutionBounds::at_most(1),
+                options.clone(),
+                budget,
+                &TestRandom,
+            );
+            assert!(gen_stats.board.is_some() || gen_stats.budget_exhausted);
+        }
+    }
+
+    // Only kings may capture queens
+    #[derive(Debug)]
+    struct RegicideQueens;
+
+    impl Variant for RegicideQueens {
+        fn name(&self) -> String {
+            "regicide".to_string()
+        }
+
+        fn can_capture(&self, piece: PieceKind, captured: PieceKind) -> bool {
+            captured != PieceKind::Queen || piece == PieceKind::King
+        }
+    }
+
+    #[test]
+    fn generator_custom_variant() {
+        let options = BoardOptions {
+            rules: Ruleset::with_age_limit(3).with(RegicideQueens),
+            ..Default::default()
+        };
+        assert_generates(options, |puzzle| {
+            for solution in &puzzle.solutions {
+                assert!(solution.iter().all(|mv| {
+                    mv.to_piece.kind != PieceKind::Queen || mv.from_piece.kind == PieceKind::King
+                }));
+            }
+        });
+    }
+
+    #[test]
+    fn generator_solo_chess() {
+        let options = BoardOptions {
+            rules: Ruleset::solo_chess(2),
+            ..Default::default()
+        };
+        assert_generates(options, |puzzle| {
+            let kings = puzzle
+                .board
+                .cells
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|piece| piece.kind == PieceKind::King)
+                .count();
+            assert_eq!(1, kings);
+
+            // The king is never captured, so it is the one left standing
+            for solution in &puzzle.solutions {
+                assert!(
+                    solution
+                        .iter()
+                        .all(|mv| mv.to_piece.kind != PieceKind::King)
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn generator_target_survivor() {
+        for target in [TargetSurvivor::Piece, TargetSurvivor::Square] {
+            for _ in 0..5 {
+                let options = BoardOptions {
+                    rules: Ruleset::with_age_limit(3),
+                    ..Default::default()
+                };
+                let gen_stats = generate_weighted_random(
+                    5,
+                    SolutionBounds::at_most(10),
+                    options,
+                    SearchBudget::unlimited(),
+                    &TestRandom,
+                );
+                let puzzle = gen_stats.puzzle().unwrap();
+                let puzzle = designate_target(
+                    puzzle,
+                    target,
+                    SolutionBounds::at_most(10),
+                    SearchBudget::unlimited(),
+                    &TestRandom,
+                )
+                .unwrap()
+                .unwrap();
+                assert!(puzzle.solved);
+
+                let board = &puzzle.board;
+                let targets = board.position().target().count_ones();
+                match target {
+                    TargetSurvivor::Piece => assert_eq!(1, targets),
+                    _ => assert!(targets == 0 && board.target_square().is_some()),
+                }
+
+                // Every solution ends on the target, which was never captured
+                for solution in &puzzle.solutions {
+                    let mut board = board.clone();
+                    for mv in solution {
+                        assert!(!mv.to_piece.target);
+                        board.make_move(mv.clone());
+                    }
+                    assert_eq!(BoardState::Won, board.game_state);
+                    let last = &solution.last().unwrap().to;
+                    match target {
+                        TargetSurvivor::Piece => {
+                            assert!(board.cells[last.file][last.rank].unwrap().target)
+                        }
+                        _ => {
+                            let square = board.target_square().unwrap();
+                            assert_eq!((last.file, last.rank), (square.file, square.rank));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generator_target_keeps_min_solutions() {
+        let bounds = SolutionBounds::new(3, 10);
+        for target in [TargetSurvivor::Piece, TargetSurvivor::Square] {
+            for _ in 0..5 {
+                let options = BoardOptions {
+                    rules: Ruleset::with_age_limit(3),
+                    ..Default::default()
+                };
+                let gen_stats = generate_weighted_random(
+                    5,
+                    bounds,
+                    options,
+                    SearchBudget::unlimited(),
+                    &TestRandom,
+                );
+                let puzzle = gen_stats.puzzle().unwrap();
+                let targeted = designate_target(
+                    puzzle,
+                    target,
+                    bounds,
+                    SearchBudget::unlimited(),
+                    &TestRandom,
+                )
+                .unwrap();
+                if let Some(puzzle) = targeted {
+                    assert!(puzzle.solutions.len() >= 3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generator_blocked_squares() {
+        // A ring around the two middle squares of a 4x4 board
+        let options = BoardOptions {
+            blocked: 1 << 5 | 1 << 6,
+            ..Default::default()
+        };
+        assert_generates(options, |puzzle| {
+            assert!(puzzle.board.cells[1][1].is_none());
+            assert!(puzzle.board.cells[2][1].is_none());
+        });
+    }
+
+    #[test]
+    fn generator_chameleon() {
+        let options = BoardOptions {
+            rules: Ruleset::with_age_limit(3).with(Chameleon),
+            ..Default::default()
+        };
+        assert_generates(options, |puzzle| {
+            // Every capturing piece carries on as the piece it captured
+            for solution in &puzzle.solutions {
+                let mut board = puzzle.board.clone();
+                for mv in solution {
+                    board.make_move(mv.clone());
+                    let piece = board.cells[mv.to.file][mv.to.rank].unwrap();
+                    assert_eq!(mv.to_piece.kind, piece.kind);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn generator_chain_capture() {
+        let options = BoardOptions {
+            rules: Ruleset::with_age_limit(4).with(ChainCapture),
+            ..Default::default()
+        };
+        assert_generates(options, |puzzle| {
+            // Every move after the first is made by the piece that just captured
+            for solution in &puzzle.solutions {
+                for (previous, mv) in solution.iter().zip(solution.iter().skip(1)) {
+                    assert_eq!(
+                        (previous.to.file, previous.to.rank),
+                        (mv.from.file, mv.from.rank)
+                    );
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn generator_torus() {
+        let options = BoardOptions {
+            topology: Topology::Torus,
+            ..Default::default()
+        };
+        assert_generates(options, |puzzle| {
+            assert_eq!(puzzle.solutions.len() as u64, puzzle.board.solution_count());
+        });
+
+        // The knight on a1 only reaches c4 around both edges
+        let board = Board::from_fen("2B1/4/4/N3 2 torus").unwrap();
+        assert_eq!(1, board.solution_count());
+        let board = Board::from_fen("2B1/4/4/N3 2 cylinder").unwrap();
+        assert_eq!(0, board.solution_count());
+    }
+
+    #[test]
+    fn generator_difficulty_target() {
+        let target = DifficultyTarget::bucket(DifficultyBucket::Hard);
+        let gen_stats = generate_with_difficulty(
+            7,
+            SolutionBounds::at_most(20),
+            BoardOptions::default(),
+            Strategy::WeightedRandom,
+            target.clone(),
+            SearchBudget::unlimited(),
+            &TestRandom,
+        );
+        let difficulty = gen_stats.difficulty().unwrap().clone();
+        assert!(target.contains(difficulty.score));
+        assert_eq!(DifficultyBucket::Hard, difficulty.bucket);
+
+        assert!(gen_stats.closest().is_none());
+        let puzzle = gen_stats.puzzle().unwrap();
+        assert!(puzzle.solved);
+        assert!(puzzle.solutions.len() <= 20);
+        assert_eq!(difficulty, puzzle.board.difficulty());
+    }
+
+    #[test]
+    fn difficulty_target_bucket() {
+        // The highest score of a bucket is the lowest of the next one
+        let buckets = [
+            DifficultyBucket::Easy,
+            DifficultyBucket::Medium,
+            DifficultyBucket::Hard,
+            DifficultyBucket::Expert,
+        ];
+        for (bucket, next) in buckets.iter().zip(buckets.iter().skip(1)) {
+            let target = DifficultyTarget::bucket(*bucket);
+            assert!(target.contains(target.min_score));
+            assert_eq!(*bucket, DifficultyBucket::from_score(target.min_score));
+            assert!(!target.contains(target.max_score));
+            assert_eq!(*next, DifficultyBucket::from_score(target.max_score));
+        }
+        assert!(!DifficultyTarget::bucket(DifficultyBucket::Medium).contains(45.0));
+    }
+
+    #[test]
+    fn generator_difficulty_out_of_reach() {
+        let target = DifficultyTarget {
+            attempts: 2,
+            ..DifficultyTarget::new(100.5, 110.0)
+        };
+        let gen_stats = generate_with_difficulty(
+            4,
+            SolutionBounds::at_most(5),
+            BoardOptions::default(),
+            Strategy::WeightedRandom,
+            target,
+            SearchBudget::unlimited(),
+            &TestRandom,
+        );
+        assert!(gen_stats.too_easy() > 0);
+        assert_eq!(0, gen_stats.too_hard());
+        assert!(gen_stats.difficulty().is_none());
+        let (board, closest) = gen_stats.closest().unwrap();
+        assert_eq!(&board.difficulty(), closest);
+        assert!(!gen_stats.budget_exhausted);
+        assert!(gen_stats.puzzle().is_none());
+
+        let gen_stats = generate_with_difficulty(
+            6,
+            SolutionBounds::at_most(5),
+            BoardOptions::default(),
+            Strategy::WeightedRandom,
+            DifficultyTarget::new(0.0, 100.0),
+            SearchBudget::unlimited().with_max_nodes(10),
+            &TestRandom,
+        );
+        assert!(gen_stats.budget_exhausted);
+    }
+
+    #[test]
+    fn generator_solution_bounds() {
+        for _ in 0..5 {
+            let gen_stats = generate_weighted_random(
+                5,
+                SolutionBounds::unique(),
+                BoardOptions::default(),
+                SearchBudget::unlimited(),
+                &TestRandom,
+            );
+            let puzzle = gen_stats.puzzle().unwrap();
+            assert_eq!(1, puzzle.solutions.len());
+
+            let bounds = SolutionBounds::new(3, 6);
+            let gen_stats = generate_weighted_random(
+                5,
+                bounds,
+                BoardOptions::default(),
+                SearchBudget::unlimited(),
+                &TestRandom,
+            );
+            let puzzle = gen_stats.puzzle().unwrap();
+            assert!(bounds.contains(puzzle.solutions.len() as u64));
+        }
+    }
+
+    #[test]
+    fn generator_merged_orders() {
+        let bounds = SolutionBounds {
+            merge_orders: true,
+            ..SolutionBounds::unique()
+        };
+        for _ in 0..5 {
+            let gen_stats = generate_weighted_random(
+                5,
+                bounds,
+                BoardOptions::default(),
+                SearchBudget::unlimited(),
+                &TestRandom,
+            );
+            let puzzle = gen_stats.puzzle().unwrap();
+            assert!(!puzzle.solutions.is_empty());
+
+            // Every solution ends with the same piece on the same square
+            let last = |solution: &Vec<CMove>| {
+                let mv = solution.last().unwrap();
+                (mv.to.file, mv.to.rank, mv.from_piece.kind)
+            };
+            let ending = last(&puzzle.solutions[0]);
+            assert!(
+                puzzle
+                    .solutions
+                    .iter()
+                    .all(|solution| last(solution) == ending)
+            );
+        }
+    }
+
+    #[test]
+    fn generator_retrograde() {
+        for _ in 0..5 {
+            let gen_stats = Strategy::Retrograde.generate(
+                6,
+                SolutionBounds::at_most(100),
+                BoardOptions {
+                    rules: Ruleset::with_age_limit(3),
+                    ..Default::default()
+                },
+                SearchBudget::unlimited(),
+                &TestRandom,
+            );
+            let puzzle = gen_stats.puzzle().unwrap();
+            assert_eq!(6, puzzle.board.position().piece_count());
+            assert!(puzzle.solved);
+
+            // Every piece is still to make its first move
+            assert!(
+                puzzle.board.occupied_squares().iter().all(|square| square
+                    .piece
+                    .unwrap()
+                    .moves_made
+                    == 0)
+            );
+        }
+    }
+
+    #[test]
+    fn generator_retrograde_blocked_target() {
+        let options = BoardOptions {
+            rules: Ruleset::with_age_limit(3).with(TargetSquare(0)),
+            blocked: 1 << 0,
+            ..Default::default()
+        };
+        let gen_stats = generate_retrograde(
+            5,
+            SolutionBounds::at_most(10),
+            options,
+            SearchBudget::unlimited(),
+            &TestRandom,
+        );
+        assert!(gen_stats.puzzle().is_none());
+    }
+
+    #[test]
+    fn generator_retrograde_rules() {
+        let options = BoardOptions {
+            rules: Ruleset::solo_chess(3).with(TargetSquare(9)),
+            blocked: 1 << 0 | 1 << 15,
+            ..Default::default()
+        };
+        assert_generates_with(Strategy::Retrograde, options, |puzzle| {
+            assert!(puzzle.board.cells[0][0].is_none());
+
+            // The king survives on b2
+            for solution in &puzzle.solutions {
+                let mut board = puzzle.board.clone();
+                for mv in solution {
+                    board.make_move(mv.clone());
+                }
+                let survivor = board.cells[1][2].unwrap();
+                assert_eq!(PieceKind::King, survivor.kind);
+            }
+        });
+    }
+
+    #[test]
+    fn generator_retrograde_custom_variant() {
+        let options = BoardOptions {
+            rules: Ruleset::solo_chess(3).with(RegicideQueens),
+            ..Default::default()
+        };
+        assert_generates_with(Strategy::Retrograde, options, |puzzle| {
+            for solution in &puzzle.solutions {
+                assert!(solution.iter().all(|mv| {
+                    mv.to_piece.kind != PieceKind::Queen || mv.from_piece.kind == PieceKind::King
+                }));
+            }
+        });
+    }
+
+    #[test]
+    fn generator_retrograde_variants() {
+        let options = BoardOptions {
+            rules: Ruleset::with_age_limit(4)
+                .with(Promotion::Choice)
+                .with(Chameleon)
+                .with(ChainCapture),
+            ..Default::default()
+        };
+        assert_generates_with(Strategy::Retrograde, options.clone(), |_| {});
+
+        // Every undone capture can be played again, so no board is left
+        // without a solution
+        for seed in 1..=5 {
+            let gen_stats = Strategy::Retrograde.generate(
+                5,
+                SolutionBounds::at_most(1000),
+                options.clone(),
+                SearchBudget::unlimited(),
+                &SeededRandom(Cell::new(seed)),
+            );
+            assert_eq!(0, gen_stats.too_few_solutions());
+            assert!(gen_stats.puzzle().is_some());
         }
     }
 }
@@ -1,2 +1,4 @@
 pub mod board;
 pub mod generator;
+pub mod record;
+pub mod solver;
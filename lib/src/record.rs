@@ -0,0 +1,354 @@
+//! Records of played games in a PGN like text format, for example
+//!
+//! ```text
+//! [Event "Daily puzzle"]
+//! [Id "wmgYAAAG"]
+//! [AgeLimit "2"]
+//! [Result "0-1"]
+//!
+//! 1. RxBb4 2. RxPa4 0-1
+//! ```
+//!
+//! The start position is given by its id and the age limit by `AgeLimit`.
+//! Results are `1-0` for a won game, `0-1` for a lost one and `*` for a game
+//! still in progress.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::board::{Board, BoardState, cmove::CMove, errors::SError};
+
+const ID_TAG: &str = "Id";
+const AGE_LIMIT_TAG: &str = "AgeLimit";
+const RESULT_TAG: &str = "Result";
+
+/// A game played from `start`. The result is always the state of the board
+/// after replaying `moves` from `start`.
+#[derive(Clone)]
+pub struct GameRecord {
+    // Metadata other than the start position, age limit and result, in the
+    // order they are written
+    pub tags: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<String>,
+    pub result: BoardState,
+}
+
+impl GameRecord {
+    pub fn new(start: Board) -> Self {
+        GameRecord {
+            tags: vec![],
+            result: start.game_state.clone(),
+            start,
+            moves: vec![],
+        }
+    }
+
+    /// Records the moves made on `board` since its pieces were last set
+    pub fn from_board(board: &Board) -> Self {
+        let mut start = board.clone();
+        start.undo_all();
+        GameRecord {
+            tags: vec![],
+            start,
+            moves: board.history().iter().map(|mv| mv.notation()).collect(),
+            result: board.game_state.clone(),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a tag, replacing its value if the tag is already present
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Makes `mv` on a replay of the game so far and records it
+    pub fn push(&mut self, mv: &CMove) -> Result<(), SError> {
+        let mut board = self.replay()?;
+        let mv = CMove::parse(&board, &mv.notation())?;
+        self.moves.push(mv.notation());
+        board.make_move(mv);
+        self.result = board.game_state;
+        Ok(())
+    }
+
+    /// The board after every recorded move has been played
+    pub fn replay(&self) -> Result<Board, SError> {
+        let mut board = self.start.clone();
+        for notation in &self.moves {
+            let mv = CMove::parse(&board, notation)?;
+            board.make_move(mv);
+        }
+
+        Ok(board)
+    }
+
+    pub fn parse(record: &str) -> Result<Self, SError> {
+        let mut tags = vec![];
+        let mut movetext = String::new();
+        for line in record.lines().map(str::trim) {
+            if line.starts_with('[') {
+                tags.push(parse_tag(line)?);
+            } else {
+                movetext.push_str(line);
+                movetext.push(' ');
+            }
+        }
+
+        let take_tag = |tags: &mut Vec<(String, String)>, name: &str| {
+            let index = tags.iter().position(|(tag, _)| tag == name)?;
+            Some(tags.remove(index).1)
+        };
+
+        let id = take_tag(&mut tags, ID_TAG)
+            .ok_or(SError::InvalidRecord(format!("Missing {} tag", ID_TAG)))?;
+        let mut start = Board::from_id(&id)?;
+        if let Some(age_limit) = take_tag(&mut tags, AGE_LIMIT_TAG) {
+            let age_limit = age_limit.parse().map_err(|_| {
+                SError::InvalidRecord(format!("Invalid {} '{}'", AGE_LIMIT_TAG, age_limit))
+            })?;
+            start = with_age_limit(&start, age_limit);
+        }
+
+        let result_tag = take_tag(&mut tags, RESULT_TAG);
+        let mut record = GameRecord {
+            tags,
+            ..GameRecord::new(start)
+        };
+
+        let mut result_token = None;
+        for token in strip_comments(&movetext).split_whitespace() {
+            if result_token.is_some() {
+                return Err(SError::InvalidRecord(format!(
+                    "Unexpected '{}' after the result",
+                    token
+                )));
+            }
+
+            if is_result(token) {
+                result_token = Some(token.to_string());
+                continue;
+            }
+
+            // Move numbers, like `1.` or `1.RxBb4`
+            let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+            if !token.is_empty() {
+                record.moves.push(token.to_string());
+            }
+        }
+
+        record.result = record.replay()?.game_state;
+        for result in [result_tag, result_token].into_iter().flatten() {
+            if result != result_notation(&record.result) {
+                return Err(SError::InvalidRecord(format!(
+                    "Result '{}' does not match the moves, which end in '{}'",
+                    result,
+                    result_notation(&record.result)
+                )));
+            }
+        }
+
+        Ok(record)
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let result = result_notation(&self.result);
+        for (name, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+        }
+
+        writeln!(f, "[{} \"{}\"]", ID_TAG, self.start.id)?;
+        writeln!(
+            f,
+            "[{} \"{}\"]",
+            AGE_LIMIT_TAG,
+            self.start.max_moves_per_piece()
+        )?;
+        writeln!(f, "[{} \"{}\"]", RESULT_TAG, result)?;
+        writeln!(f)?;
+
+        for (idx, notation) in self.moves.iter().enumerate() {
+            write!(f, "{}. {} ", idx + 1, notation)?;
+        }
+
+        writeln!(f, "{}", result)
+    }
+}
+
+fn result_notation(state: &BoardState) -> &'static str {
+    match state {
+        BoardState::Won => "1-0",
+        BoardState::Lost => "0-1",
+        BoardState::NotStarted | BoardState::InProgress => "*",
+    }
+}
+
+fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "*")
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), SError> {
+    let invalid = || SError::InvalidRecord(format!("Invalid tag {}", line));
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(invalid)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+
+    Ok((name.to_string(), unescape(value)))
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+/// Drops `{ ... }` comments from the movetext
+fn strip_comments(movetext: &str) -> String {
+    let mut stripped = String::new();
+    let mut depth = 0;
+    for c in movetext.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            c if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    stripped
+}
+
+/// `board` with a different age limit, keeping the age of every piece
+fn with_age_limit(board: &Board, max_moves_per_piece: u32) -> Board {
+    let mut rebuilt = board.clone();
+    let mut rules = board.rules().clone();
+    rules.max_moves_per_piece = max_moves_per_piece;
+    rebuilt.set_rules(rules);
+
+    rebuilt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played(board_string: &str, moves: &[&str]) -> Board {
+        let mut board = Board::from_string(board_string.to_string()).unwrap();
+        for notation in moves {
+            let mv = CMove::parse(&board, notation).unwrap();
+            board.make_move(mv);
+        }
+        board
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let board = played("pb......br..p..p", &["Rxb4", "Rxa4"]);
+        let mut record = GameRecord::from_board(&board);
+        record.set_tag("Event", "Bug \"report\"");
+        record.set_tag("Player", "anon");
+        assert_eq!(BoardState::Lost, record.result);
+        assert_eq!(vec!["RxBb4", "RxPa4"], record.moves);
+
+        let text = record.to_string();
+        assert_eq!(
+            "[Event \"Bug \\\"report\\\"\"]\n\
+             [Player \"anon\"]\n\
+             [Id \"wmgYAAAG\"]\n\
+             [AgeLimit \"2\"]\n\
+             [Result \"0-1\"]\n\
+             \n\
+             1. RxBb4 2. RxPa4 0-1\n",
+            text
+        );
+
+        let parsed = GameRecord::parse(&text).unwrap();
+        assert_eq!(Some("Bug \"report\""), parsed.tag("Event"));
+        assert_eq!(record.tags, parsed.tags);
+        assert_eq!(record.moves, parsed.moves);
+        assert_eq!(record.result, parsed.result);
+        assert_eq!(board.cells, parsed.replay().unwrap().cells);
+    }
+
+    #[test]
+    fn test_record_won_game() {
+        let board = played("n......r.", &["Nxb1"]);
+        let record = GameRecord::from_board(&board);
+        assert_eq!(BoardState::Won, record.result);
+        let parsed = GameRecord::parse(&record.to_string()).unwrap();
+        assert_eq!(BoardState::Won, parsed.result);
+        assert_eq!(3, parsed.start.width);
+    }
+
+    #[test]
+    fn test_record_push() {
+        let start = Board::from_string("pb......br..p..p".to_string()).unwrap();
+        let mut record = GameRecord::new(start.clone());
+        assert_eq!(BoardState::InProgress, record.result);
+
+        let mv = CMove::parse(&start, "Rxb4").unwrap();
+        record.push(&mv).unwrap();
+        assert_eq!(vec!["RxBb4"], record.moves);
+        assert!(record.push(&mv).is_err());
+        assert!(record.to_string().ends_with("1. RxBb4 *\n"));
+    }
+
+    #[test]
+    fn test_record_parse() {
+        let record = GameRecord::parse(
+            "[Id \"wmgYAAAG\"]\n\
+             [AgeLimit \"5\"]\n\
+             \n\
+             1.Rxb4 {a comment} 2. Rxa4 3. Rxa2 *",
+        )
+        .unwrap();
+        assert_eq!(5, record.start.max_moves_per_piece());
+        assert_eq!(3, record.moves.len());
+        assert_eq!(BoardState::InProgress, record.result);
+        assert!(record.tags.is_empty());
+
+        let invalid = |text: &str| match GameRecord::parse(text) {
+            Err(SError::InvalidRecord(_)) => {}
+            _ => panic!("expected an invalid record: {}", text),
+        };
+        invalid("1. Rxb4 *");
+        invalid("[Id \"wmgYAAAG\"]\n1. Rxb4 1-0");
+        invalid("[Id \"wmgYAAAG\"]\n[Result \"1-0\"]\n1. Rxb4");
+        invalid("[Id \"wmgYAAAG\"]\n1. Rxb4 * Rxa4");
+        invalid("[Id wmgYAAAG]");
+        invalid("[Id \"wmgYAAAG\"]\n[AgeLimit \"two\"]");
+
+        assert_eq!(
+            Err(SError::IllegalMove("Rxc4".to_string())),
+            GameRecord::parse("[Id \"wmgYAAAG\"]\n1. Rxc4").map(|r| r.moves)
+        );
+    }
+}
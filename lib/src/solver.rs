@@ -0,0 +1,490 @@
+pub mod budget;
+pub mod difficulty;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use budget::{BudgetExhausted, SearchBudget};
+
+use crate::{
+    board::{
+        Board,
+        cmove::CMove,
+        piece::PieceKind,
+        position::{AttackTables, Position, PositionMove},
+        ruleset::Ruleset,
+    },
+    generator::Puzzle,
+};
+
+/// Depth first search over bitboard positions. The number of solutions
+/// reachable from every position visited is cached, so positions reached
+/// through different capture orders are only explored once.
+///
+/// A solver can be reused for any board with the same shape and rules as the
+/// one it was created for. Once its budget is spent, every search
+/// returns `BudgetExhausted`.
+pub struct Solver {
+    tables: Arc<AttackTables>,
+    rules: Ruleset,
+    solution_counts: HashMap<Position, SolutionCount>,
+    budget: SearchBudget,
+    nodes: u64,
+}
+
+/// Lazily yields the solutions of a board, one at a time and in the order
+/// the depth first search finds them. Iteration stops early when the
+/// solver's budget runs out, which `budget_exhausted` tells apart from
+/// having seen every solution.
+pub struct Solutions<'a> {
+    board: &'a Board,
+    solver: Solver,
+    search: SolutionSearch,
+    budget_exhausted: bool,
+}
+
+struct SolutionSearch {
+    stack: Vec<StackItem>,
+}
+
+struct StackItem {
+    position: Position,
+    moves_so_far: Vec<PositionMove>,
+}
+
+// Counting stops once a limit is reached, in which case the cached count is
+// only a lower bound.
+#[derive(Clone, Copy)]
+struct SolutionCount {
+    count: u64,
+    exact: bool,
+}
+
+impl Solver {
+    pub fn new(board: &Board) -> Self {
+        Solver::with_budget(board, SearchBudget::unlimited())
+    }
+
+    pub fn with_budget(board: &Board, budget: SearchBudget) -> Self {
+        Solver {
+            tables: board.tables(),
+            rules: board.rules().clone(),
+            solution_counts: HashMap::new(),
+            budget,
+            nodes: 0,
+        }
+    }
+
+    /// Number of positions expanded so far
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Number of move sequences from `position` that end in a won game.
+    /// Saturates at `u64::MAX`.
+    pub fn count(&mut self, position: &Position) -> Result<u64, BudgetExhausted> {
+        self.count_solutions(position, u64::MAX)
+    }
+
+    /// Like `count`, but stops searching as soon as `limit` solutions are
+    /// found and returns at most `limit`.
+    pub fn count_solutions(
+        &mut self,
+        position: &Position,
+        limit: u64,
+    ) -> Result<u64, BudgetExhausted> {
+        if limit == 0 {
+            return Ok(0);
+        }
+
+        if self.rules.is_won(position) {
+            return Ok(1);
+        }
+
+        if let Some(cached) = self.solution_counts.get(position)
+            && (cached.exact || cached.count >= limit)
+        {
+            return Ok(cached.count.min(limit));
+        }
+
+        self.budget.check(self.nodes + 1)?;
+        self.nodes += 1;
+
+        // Leaving early on an exhausted budget skips caching the partial count
+        let mut total: u64 = 0;
+        for mv in self.legal_moves(position) {
+            let mut next = *position;
+            next.make_move(mv, &self.rules);
+            total += self.count_solutions(&next, limit - total)?;
+            if total == limit {
+                break;
+            }
+        }
+
+        // Reaching the limit means some line may have been cut short
+        let exact = total < limit;
+        self.solution_counts.insert(
+            *position,
+            SolutionCount {
+                count: total,
+                exact,
+            },
+        );
+        Ok(total)
+    }
+
+    /// Like `count_solutions`, but solutions that leave the same last piece
+    /// on the same square, in whatever order the captures were made, count
+    /// as one.
+    pub fn count_endings(
+        &mut self,
+        position: &Position,
+        limit: u64,
+    ) -> Result<u64, BudgetExhausted> {
+        let mut endings = HashSet::new();
+        self.collect_endings(position, limit, &mut HashSet::new(), &mut endings)?;
+        Ok((endings.len() as u64).min(limit))
+    }
+
+    fn collect_endings(
+        &mut self,
+        position: &Position,
+        limit: u64,
+        visited: &mut HashSet<Position>,
+        endings: &mut HashSet<(u32, Option<PieceKind>)>,
+    ) -> Result<(), BudgetExhausted> {
+        if endings.len() as u64 >= limit || !visited.insert(*position) {
+            return Ok(());
+        }
+
+        if self.rules.is_won(position) {
+            let sq = position.occupied().trailing_zeros();
+            endings.insert((sq, position.kind_at(sq as usize)));
+            return Ok(());
+        }
+
+        if !self.is_solvable(position)? {
+            return Ok(());
+        }
+
+        for mv in self.legal_moves(position) {
+            let mut next = *position;
+            next.make_move(mv, &self.rules);
+            self.collect_endings(&next, limit, visited, endings)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_solvable(&mut self, position: &Position) -> Result<bool, BudgetExhausted> {
+        Ok(self.count_solutions(position, 1)? > 0)
+    }
+
+    /// The first solution found from `board`, following only the moves
+    /// that still lead to a solution.
+    pub fn solve_first(&mut self, board: &Board) -> Result<Option<Vec<CMove>>, BudgetExhausted> {
+        let mut position = board.position();
+        if !self.is_solvable(&position)? {
+            return Ok(None);
+        }
+
+        let mut moves = Vec::new();
+        while !self.rules.is_won(&position) {
+            let mut solvable_move = None;
+            for mv in self.legal_moves(&position) {
+                let mut next = position;
+                next.make_move(mv, &self.rules);
+                if self.is_solvable(&next)? {
+                    solvable_move = Some(mv);
+                    break;
+                }
+            }
+
+            let Some(mv) = solvable_move else {
+                return Ok(None);
+            };
+
+            position.make_move(mv, &self.rules);
+            moves.push(mv);
+        }
+
+        Ok(Some(self.to_cmoves(board, &moves)))
+    }
+
+    /// Every solution of `board`, skipping the lines that the cached counts
+    /// show to be dead ends.
+    pub fn solve(&mut self, board: &Board) -> Result<Puzzle, BudgetExhausted> {
+        let mut search = SolutionSearch::new(board);
+        let mut solutions = Vec::new();
+        while let Some(moves) = search.next(self)? {
+            solutions.push(self.to_cmoves(board, &moves));
+        }
+
+        let solved = !solutions.is_empty();
+        Ok(Puzzle {
+            board: board.clone(),
+            solutions,
+            solved,
+        })
+    }
+
+    /// Turns the solver into an iterator over the solutions of `board`
+    pub fn solutions(self, board: &Board) -> Solutions<'_> {
+        Solutions {
+            board,
+            solver: self,
+            search: SolutionSearch::new(board),
+            budget_exhausted: false,
+        }
+    }
+
+    fn legal_moves(&self, position: &Position) -> Vec<PositionMove> {
+        let mut moves = Vec::new();
+        position.legal_moves(&self.tables, &self.rules, &mut moves);
+        moves
+    }
+
+    /// Replays a line of moves from `board`, translating each one to the
+    /// `CMove` the board would offer at that point.
+    fn to_cmoves(&self, board: &Board, moves: &[PositionMove]) -> Vec<CMove> {
+        let mut position = board.position();
+        moves
+            .iter()
+            .map(|mv| {
+                let cmove = position.to_cmove(*mv, &self.tables, &self.rules);
+                position.make_move(*mv, &self.rules);
+                cmove
+            })
+            .collect()
+    }
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Vec<CMove>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Ok(moves) = self.search.next(&mut self.solver) else {
+            self.budget_exhausted = true;
+            return None;
+        };
+
+        Some(self.solver.to_cmoves(self.board, &moves?))
+    }
+}
+
+impl Solutions<'_> {
+    /// Whether the iteration ended because the budget ran out
+    pub fn budget_exhausted(&self) -> bool {
+        self.budget_exhausted
+    }
+
+    /// Number of positions expanded so far
+    pub fn nodes(&self) -> u64 {
+        self.solver.nodes()
+    }
+}
+
+/// Unwraps the result of a search that was given no budget, and so always
+/// runs to completion.
+pub(crate) fn unbounded<T>(result: Result<T, BudgetExhausted>) -> T {
+    result.expect("search without a budget ran out of budget")
+}
+
+impl SolutionSearch {
+    fn new(board: &Board) -> Self {
+        SolutionSearch {
+            stack: vec![StackItem {
+                position: board.position(),
+                moves_so_far: vec![],
+            }],
+        }
+    }
+
+    /// Resumes the search where the previous solution was found. Only moves
+    /// that still lead to a solution are pushed on the stack, so every line
+    /// popped from it ends in a solution.
+    fn next(&mut self, solver: &mut Solver) -> Result<Option<Vec<PositionMove>>, BudgetExhausted> {
+        while let Some(top) = self.stack.pop() {
+            let (position, moves_so_far) = (top.position, top.moves_so_far);
+            if solver.rules.is_won(&position) {
+                return Ok(Some(moves_so_far));
+            }
+
+            for mv in solver.legal_moves(&position) {
+                let mut next = position;
+                next.make_move(mv, &solver.rules);
+                if !solver.is_solvable(&next)? {
+                    continue;
+                }
+
+                let mut moves_so_far = moves_so_far.clone();
+                moves_so_far.push(mv);
+                self.stack.push(StackItem {
+                    position: next,
+                    moves_so_far,
+                });
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{BoardOptions, BoardState, ruleset::Ruleset, square::Square};
+
+    use super::*;
+
+    fn naive_count(board: &Board) -> u64 {
+        if board.position().piece_count() == 1 {
+            return 1;
+        }
+
+        board
+            .legal_moves
+            .iter()
+            .map(|mv| {
+                let mut next = board.clone();
+                next.make_move(mv.clone());
+                naive_count(&next)
+            })
+            .sum()
+    }
+
+    #[test]
+    fn solver_counts_transpositions() {
+        // R . . R
+        // . N . .
+        // . . B .
+        // R . Q R
+        let board = Board::from_string("r..r.n....b.r.qr".to_string()).unwrap();
+        let mut solver = Solver::new(&board);
+        let count = solver.count(&board.position()).unwrap();
+        assert!(count > 0);
+        assert_eq!(naive_count(&board), count);
+        assert_eq!(
+            count as usize,
+            solver.solve(&board).unwrap().solutions.len()
+        );
+    }
+
+    #[test]
+    fn solver_count_matches_solutions() {
+        let mut board = Board::create(BoardOptions {
+            rules: Ruleset::with_age_limit(4),
+            ..Default::default()
+        });
+        for sq in ["Pa1", "Ba2", "Ra3", "Rb4", "Nc1", "Bc2", "Nd2", "Pd3"] {
+            board.set(Square::parse(sq, 4, 4).unwrap());
+        }
+
+        let mut solver = Solver::new(&board);
+        let puzzle = solver.solve(&board).unwrap();
+        assert!(puzzle.solved);
+        assert_eq!(
+            puzzle.solutions.len() as u64,
+            solver.count(&board.position()).unwrap()
+        );
+        assert_eq!(puzzle.solutions.len() as u64, board.solution_count());
+        assert!(board.is_solvable());
+
+        for solution in puzzle.solutions {
+            let mut replay = board.clone();
+            for mv in solution {
+                assert!(replay.make_move(mv).is_some());
+            }
+            assert_eq!(1, replay.position().piece_count());
+        }
+    }
+
+    #[test]
+    fn solver_early_exit() {
+        let board = Board::from_string("r..r.n....b.r.qr".to_string()).unwrap();
+        let total = board.solution_count();
+        assert!(total > 3);
+
+        let mut solver = Solver::new(&board);
+        let position = board.position();
+        assert_eq!(Ok(3), solver.count_solutions(&position, 3));
+        assert_eq!(Ok(0), solver.count_solutions(&position, 0));
+        // Counts cut short by a limit are not reused as exact counts
+        assert_eq!(Ok(total), solver.count_solutions(&position, u64::MAX));
+        assert_eq!(total, board.count_solutions(total + 1));
+
+        let solution = board.solve_first().expect("board is solvable");
+        let mut replay = board.clone();
+        for mv in solution {
+            assert!(replay.make_move(mv).is_some());
+        }
+        assert_eq!(BoardState::Won, replay.game_state);
+    }
+
+    #[test]
+    fn solver_early_exit_no_solution() {
+        // . R . .
+        // R . . .
+        // B . B N
+        // P . N .
+        let board = Board::from_string(".r..r...b.bnp.n.".to_string()).unwrap();
+        assert!(!board.is_solvable());
+        assert!(board.solve_first().is_none());
+        assert_eq!(0, board.count_solutions(10));
+    }
+
+    #[test]
+    fn solutions_iterator() {
+        let board = Board::from_string("r..r.n....b.r.qr".to_string()).unwrap();
+        let mut solutions = board.solutions();
+        let first = solutions.next().expect("board is solvable");
+        assert_eq!(board.solve_first().unwrap().len(), first.len());
+        assert_eq!(board.solution_count() as usize, 1 + solutions.count());
+
+        let collected = board.solutions().collect::<Vec<_>>();
+        assert_eq!(board.solve().solutions.len(), collected.len());
+        assert!(collected.contains(&first));
+    }
+
+    #[test]
+    fn solver_budget_exhausted() {
+        let board = Board::from_string("r..r.n....b.r.qr".to_string()).unwrap();
+        let budget = SearchBudget::unlimited().with_max_nodes(5);
+        let mut solver = Solver::with_budget(&board, budget.clone());
+        let position = board.position();
+        assert_eq!(Err(BudgetExhausted), solver.count(&position));
+        assert_eq!(Err(BudgetExhausted), solver.is_solvable(&position));
+        assert!(solver.solve(&board).is_err());
+        assert!(board.solve_within(budget.clone()).is_err());
+
+        let mut solutions = Solver::with_budget(&board, budget).solutions(&board);
+        assert!(solutions.next().is_none());
+        assert!(solutions.budget_exhausted());
+
+        // An unsolvable board is reported as such within a large enough budget
+        let board = Board::from_string(".r..r...b.bnp.n.".to_string()).unwrap();
+        let budget = SearchBudget::unlimited().with_max_nodes(1000);
+        let mut solver = Solver::with_budget(&board, budget);
+        assert_eq!(Ok(false), solver.is_solvable(&board.position()));
+        assert!(solver.nodes() > 0);
+    }
+
+    #[test]
+    fn solver_count_endings() {
+        // Three rooks on a row can be cleared up in eight orders, which leave
+        // the last rook on one of three squares
+        let board = Board::from_fen("4/4/4/RRR1 2").unwrap();
+        let mut solver = Solver::new(&board);
+        let position = board.position();
+        assert_eq!(Ok(8), solver.count(&position));
+        assert_eq!(Ok(3), solver.count_endings(&position, u64::MAX));
+        assert_eq!(Ok(2), solver.count_endings(&position, 2));
+
+        let board = Board::from_string(".r..r...b.bnp.n.".to_string()).unwrap();
+        assert_eq!(
+            Ok(0),
+            Solver::new(&board).count_endings(&board.position(), 5)
+        );
+    }
+}
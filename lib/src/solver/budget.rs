@@ -0,0 +1,140 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+// The clock and the cancellation token are only looked at every so often,
+// and when a search starts
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Limits on how much work a search may do before giving up. The default
+/// budget is unlimited.
+///
+/// Wall clock limits are not available on `wasm32-unknown-unknown`, where
+/// `Instant::now` panics, use a node limit there instead.
+#[derive(Clone, Default, Debug)]
+pub struct SearchBudget {
+    // Number of positions that may be expanded
+    pub max_nodes: Option<u64>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelToken>,
+}
+
+/// Shared flag that stops every search holding a clone of it
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+/// Returned instead of a result when a search ran out of budget before it
+/// could give a definite answer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetExhausted;
+
+impl SearchBudget {
+    pub fn unlimited() -> Self {
+        SearchBudget::default()
+    }
+
+    pub fn with_max_nodes(mut self, max_nodes: u64) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Gives up `limit` from now
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.deadline = Some(Instant::now() + limit);
+        self
+    }
+
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// The budget left once `nodes` positions have been expanded
+    pub fn remaining(&self, nodes: u64) -> Self {
+        SearchBudget {
+            max_nodes: self.max_nodes.map(|max| max.saturating_sub(nodes)),
+            ..self.clone()
+        }
+    }
+
+    /// Checks the budget before expanding the `nodes`-th position
+    pub(crate) fn check(&self, nodes: u64) -> Result<(), BudgetExhausted> {
+        if let Some(max_nodes) = self.max_nodes
+            && nodes > max_nodes
+        {
+            return Err(BudgetExhausted);
+        }
+
+        if nodes != 1 && !nodes.is_multiple_of(CHECK_INTERVAL) {
+            return Ok(());
+        }
+
+        if let Some(cancel) = &self.cancel
+            && cancel.is_cancelled()
+        {
+            return Err(BudgetExhausted);
+        }
+
+        if let Some(deadline) = self.deadline
+            && Instant::now() >= deadline
+        {
+            return Err(BudgetExhausted);
+        }
+
+        Ok(())
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Display for BudgetExhausted {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Search budget exhausted")
+    }
+}
+
+impl std::error::Error for BudgetExhausted {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_checks() {
+        let budget = SearchBudget::unlimited();
+        assert!(budget.check(u64::MAX).is_ok());
+
+        let budget = SearchBudget::unlimited().with_max_nodes(10);
+        assert!(budget.check(10).is_ok());
+        assert_eq!(Err(BudgetExhausted), budget.check(11));
+        assert_eq!(Err(BudgetExhausted), budget.remaining(5).check(6));
+
+        let cancel = CancelToken::new();
+        let budget = SearchBudget::unlimited().with_cancel_token(cancel.clone());
+        assert!(budget.check(CHECK_INTERVAL).is_ok());
+        cancel.cancel();
+        assert!(budget.check(2).is_ok());
+        assert_eq!(Err(BudgetExhausted), budget.check(1));
+        assert_eq!(Err(BudgetExhausted), budget.check(CHECK_INTERVAL));
+
+        let budget = SearchBudget::unlimited().with_time_limit(Duration::ZERO);
+        assert_eq!(Err(BudgetExhausted), budget.check(CHECK_INTERVAL));
+    }
+}
@@ -0,0 +1,321 @@
+//! How hard a puzzle is to solve, measured on its whole search tree rather
+//! than on the number of pieces.
+//!
+//! Every capture removes a piece, so all the lines that reach a position do
+//! so after the same number of moves and the tree can be walked one ply at a
+//! time. Lines are counted with their multiplicity, a position reached in
+//! three ways counts three times.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+use super::{Solver, budget::BudgetExhausted};
+use crate::board::{Board, position::Position};
+
+/// The rating of a puzzle along with the measurements it is made of
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    /// From 0 for a puzzle that solves itself up to 100
+    pub score: f64,
+    pub bucket: DifficultyBucket,
+
+    /// Lines that end in a won game
+    pub solutions: u64,
+
+    /// Lines that end because no capture is left, won or not
+    pub leaf_paths: u64,
+
+    /// Per ply, the share of the moves available in positions that can still
+    /// be won which lead to positions that can not
+    pub dead_end_ratios: Vec<f64>,
+
+    /// Plies at which every position that can still be won has a single
+    /// legal move
+    pub forced_moves: u32,
+
+    /// How many more moves a losing move lets the player make before they
+    /// run out of captures, on average
+    pub reveal_depth: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DifficultyBucket {
+    Unsolvable,
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+// Lower bounds of the scores of the buckets above easy
+const MEDIUM_SCORE: f64 = 25.0;
+const HARD_SCORE: f64 = 45.0;
+const EXPERT_SCORE: f64 = 65.0;
+
+impl Solver {
+    /// Rates `board` by walking every line of play from it, losing ones
+    /// included.
+    pub fn rate(&mut self, board: &Board) -> Result<Difficulty, BudgetExhausted> {
+        let mut solutions: u64 = 0;
+        let mut leaf_paths: u64 = 0;
+        let mut dead_end_ratios = vec![];
+        let mut forced_moves = 0;
+        let (mut losing_moves, mut reveal_total) = (0.0, 0.0);
+        let mut longest_lines = HashMap::new();
+
+        let mut ply = HashMap::from([(board.position(), 1u64)]);
+        while !ply.is_empty() {
+            let mut next_ply: HashMap<Position, u64> = HashMap::new();
+            let (mut moves_total, mut dead_ends) = (0.0, 0.0);
+            let mut forced = true;
+            let mut any_solvable = false;
+            for (position, lines) in ply {
+                if self.rules.is_won(&position) {
+                    solutions = solutions.saturating_add(lines);
+                    leaf_paths = leaf_paths.saturating_add(lines);
+                    continue;
+                }
+
+                let moves = self.legal_moves(&position);
+                if moves.is_empty() {
+                    leaf_paths = leaf_paths.saturating_add(lines);
+                    continue;
+                }
+
+                self.budget.check(self.nodes + 1)?;
+                self.nodes += 1;
+
+                let solvable = self.is_solvable(&position)?;
+                if solvable {
+                    any_solvable = true;
+                    forced &= moves.len() == 1;
+                    moves_total += (lines as f64) * moves.len() as f64;
+                }
+
+                for mv in moves {
+                    let mut next = position;
+                    next.make_move(mv, &self.rules);
+                    if solvable && !self.is_solvable(&next)? {
+                        dead_ends += lines as f64;
+                        losing_moves += lines as f64;
+                        reveal_total +=
+                            (lines as f64) * self.longest_line(&next, &mut longest_lines)? as f64;
+                    }
+
+                    let next_lines = next_ply.entry(next).or_default();
+                    *next_lines = next_lines.saturating_add(lines);
+                }
+            }
+
+            if any_solvable {
+                dead_end_ratios.push(dead_ends / moves_total);
+                if forced {
+                    forced_moves += 1;
+                }
+            }
+
+            ply = next_ply;
+        }
+
+        let reveal_depth = match losing_moves {
+            0.0 => 0.0,
+            _ => reveal_total / losing_moves,
+        };
+        let score = match solutions {
+            0 => 0.0,
+            _ => score(
+                solutions,
+                leaf_paths,
+                &dead_end_ratios,
+                forced_moves,
+                reveal_depth,
+            ),
+        };
+        let bucket = match solutions {
+            0 => DifficultyBucket::Unsolvable,
+            _ => DifficultyBucket::from_score(score),
+        };
+
+        Ok(Difficulty {
+            score,
+            bucket,
+            solutions,
+            leaf_paths,
+            dead_end_ratios,
+            forced_moves,
+            reveal_depth,
+        })
+    }
+
+    /// Number of moves in the longest line from `position`, which can not
+    /// be won
+    fn longest_line(
+        &mut self,
+        position: &Position,
+        longest_lines: &mut HashMap<Position, u32>,
+    ) -> Result<u32, BudgetExhausted> {
+        if let Some(longest) = longest_lines.get(position) {
+            return Ok(*longest);
+        }
+
+        self.budget.check(self.nodes + 1)?;
+        self.nodes += 1;
+
+        let mut longest = 0;
+        for mv in self.legal_moves(position) {
+            let mut next = *position;
+            next.make_move(mv, &self.rules);
+            longest = longest.max(1 + self.longest_line(&next, longest_lines)?);
+        }
+
+        longest_lines.insert(*position, longest);
+        Ok(longest)
+    }
+}
+
+/// Weighs four measurements that each range from 0 to 1:
+///
+/// - how likely a random move is to keep the game winnable, as the
+///   geometric mean over the plies of the share of lines that are solutions
+/// - how many of the moves open to the player are dead ends
+/// - how long a losing move hides that it lost, compared to the length of
+///   the game
+/// - how few of the moves are forced
+fn score(
+    solutions: u64,
+    leaf_paths: u64,
+    dead_end_ratios: &[f64],
+    forced_moves: u32,
+    reveal_depth: f64,
+) -> f64 {
+    let plies = dead_end_ratios.len().max(1) as f64;
+    let luck = 1.0 - (solutions as f64 / leaf_paths as f64).powf(1.0 / plies);
+    let dead_ends = dead_end_ratios.iter().sum::<f64>() / plies;
+    let reveal = (reveal_depth / (plies - 1.0).max(1.0)).min(1.0);
+    let choice = 1.0 - forced_moves as f64 / plies;
+
+    100.0 * (0.35 * luck + 0.3 * dead_ends + 0.2 * reveal + 0.15 * choice)
+}
+
+impl DifficultyBucket {
+    /// The bucket of a puzzle that has a solution
+    pub fn from_score(score: f64) -> Self {
+        if score >= EXPERT_SCORE {
+            DifficultyBucket::Expert
+        } else if score >= HARD_SCORE {
+            DifficultyBucket::Hard
+        } else if score >= MEDIUM_SCORE {
+            DifficultyBucket::Medium
+        } else {
+            DifficultyBucket::Easy
+        }
+    }
+
+    /// The lowest and highest score of the puzzles in this bucket
+    pub fn score_range(&self) -> (f64, f64) {
+        match self {
+            DifficultyBucket::Unsolvable => (0.0, 0.0),
+            DifficultyBucket::Easy => (0.0, MEDIUM_SCORE),
+            DifficultyBucket::Medium => (MEDIUM_SCORE, HARD_SCORE),
+            DifficultyBucket::Hard => (HARD_SCORE, EXPERT_SCORE),
+            DifficultyBucket::Expert => (EXPERT_SCORE, 100.0),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(DifficultyBucket::Easy),
+            "medium" => Some(DifficultyBucket::Medium),
+            "hard" => Some(DifficultyBucket::Hard),
+            "expert" => Some(DifficultyBucket::Expert),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DifficultyBucket {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            DifficultyBucket::Unsolvable => "unsolvable",
+            DifficultyBucket::Easy => "easy",
+            DifficultyBucket::Medium => "medium",
+            DifficultyBucket::Hard => "hard",
+            DifficultyBucket::Expert => "expert",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:.1} ({})", self.score, self.bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board::Board,
+        solver::budget::{BudgetExhausted, SearchBudget},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_forced_puzzle() {
+        // A single capture that can not go wrong
+        let difficulty = Board::from_fen("4/4/4/R2N 2").unwrap().difficulty();
+        assert_eq!(1, difficulty.solutions);
+        assert_eq!(1, difficulty.leaf_paths);
+        assert_eq!(vec![0.0], difficulty.dead_end_ratios);
+        assert_eq!(1, difficulty.forced_moves);
+        assert_eq!(0.0, difficulty.reveal_depth);
+        assert_eq!(0.0, difficulty.score);
+        assert_eq!(DifficultyBucket::Easy, difficulty.bucket);
+
+        // Either rook can take the other, which is still no real choice
+        let difficulty = Board::from_fen("4/4/4/R2R 2").unwrap().difficulty();
+        assert_eq!(0, difficulty.forced_moves);
+        assert_eq!(DifficultyBucket::Easy, difficulty.bucket);
+    }
+
+    #[test]
+    fn test_dead_ends() {
+        // R N . .
+        // . . . .
+        // B . . .
+        // P . . Q
+        // Of the five first captures only RxNb4 loses, the other pieces can
+        // not all be cleared up after it
+        let board = Board::from_fen("RN2/4/B3/P2Q 3").unwrap();
+        let difficulty = board.difficulty();
+        assert_eq!(board.solution_count(), difficulty.solutions);
+        assert!(difficulty.leaf_paths > difficulty.solutions);
+        assert!(difficulty.dead_end_ratios[0] > 0.0);
+        assert!(difficulty.reveal_depth > 0.0);
+        assert!(difficulty.score > 0.0);
+        assert!(difficulty.score <= 100.0);
+        assert_eq!(
+            DifficultyBucket::from_score(difficulty.score),
+            difficulty.bucket
+        );
+
+        let board = Board::from_string(".r..r...b.bnp.n.".to_string()).unwrap();
+        let difficulty = board.difficulty();
+        assert_eq!(0, difficulty.solutions);
+        assert_eq!(DifficultyBucket::Unsolvable, difficulty.bucket);
+    }
+
+    #[test]
+    fn test_difficulty_budget() {
+        let board = Board::from_string("r..r.n....b.r.qr".to_string()).unwrap();
+        let budget = SearchBudget::unlimited().with_max_nodes(5);
+        assert_eq!(Err(BudgetExhausted), board.difficulty_within(budget));
+
+        let difficulty = board.difficulty();
+        assert_eq!(board.solution_count(), difficulty.solutions);
+        assert!(difficulty.bucket > DifficultyBucket::Unsolvable);
+    }
+}